@@ -0,0 +1,208 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attachments::{AttachmentDownloader, AttachmentOptions, DownloadStatus},
+    error::{IoContext, Result},
+    transport::Transport,
+    Message,
+};
+
+/// Output formats of a message archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Full message objects, one JSON document per line
+    Json,
+    /// One flat row per message
+    Csv,
+    /// Readable static transcript
+    Html,
+}
+
+const JSON_FILE: &str = "messages.jsonl";
+const CSV_FILE: &str = "messages.csv";
+const HTML_FILE: &str = "transcript.html";
+
+const CSV_HEADER: [&str; 8] = [
+    "message_id", "timestamp", "channel_id", "author_id", "content", "attachments", "embeds", "reply_to",
+];
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Message archive</title>
+<style>
+body { font-family: sans-serif; background: #313338; color: #dbdee1; max-width: 900px; margin: 2em auto; }
+.message { border-bottom: 1px solid #3f4147; padding: 0.6em 0; }
+.meta { color: #949ba4; font-size: 0.85em; }
+.content { white-space: pre-wrap; margin-top: 0.3em; }
+a { color: #00a8fc; }
+</style>
+</head>
+<body>
+"#;
+
+/// Writes every archived message to disk immediately, before it is deleted.
+///
+/// Files are opened in append mode so resumed runs extend the existing archive, and every
+/// write is flushed so a crash never loses a message that was already deleted.
+pub struct Archive {
+    dir: PathBuf,
+    channel_id: String,
+    json: Option<BufWriter<File>>,
+    csv: Option<csv::Writer<File>>,
+    html: Option<BufWriter<File>>,
+    attachments: Option<AttachmentDownloader>,
+}
+
+impl Archive {
+    pub fn open(
+        root: &Path,
+        channel_id: &str,
+        formats: &[ArchiveFormat],
+        attachments: Option<AttachmentOptions>,
+        transport: &Transport,
+    ) -> Result<Self> {
+        let dir = root.join(channel_id);
+        fs::create_dir_all(&dir)
+            .io_context(|| format!("Failed to create archive directory {}", dir.display()))?;
+
+        let mut archive = Self {
+            dir,
+            channel_id: channel_id.to_string(),
+            json: None,
+            csv: None,
+            html: None,
+            attachments: None,
+        };
+
+        if let Some(options) = attachments {
+            archive.attachments = Some(AttachmentDownloader::open(&archive.dir, options, transport.clone())?);
+        }
+
+        if formats.contains(&ArchiveFormat::Json) {
+            archive.json = Some(BufWriter::new(archive.append(JSON_FILE)?.0));
+        }
+
+        if formats.contains(&ArchiveFormat::Csv) {
+            let (file, is_new) = archive.append(CSV_FILE)?;
+            let mut writer = csv::Writer::from_writer(file);
+            if is_new {
+                writer.write_record(CSV_HEADER)?;
+                writer.flush()?;
+            }
+            archive.csv = Some(writer);
+        }
+
+        if formats.contains(&ArchiveFormat::Html) {
+            let (file, is_new) = archive.append(HTML_FILE)?;
+            let mut writer = BufWriter::new(file);
+            if is_new {
+                writer.write_all(HTML_HEADER.as_bytes())?;
+                writer.flush()?;
+            }
+            archive.html = Some(writer);
+        }
+
+        Ok(archive)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn append(&self, name: &str) -> Result<(File, bool)> {
+        let path = self.dir.join(name);
+        let is_new = !path.exists() || fs::metadata(&path)?.len() == 0;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .io_context(|| format!("Failed to open archive file {}", path.display()))?;
+        Ok((file, is_new))
+    }
+
+    /// Downloads the message's attachments (when enabled) and then appends it to every format.
+    pub async fn write(&mut self, message: &Message) -> Result<()> {
+        let downloads = match &mut self.attachments {
+            Some(downloader) => downloader.download(message).await?,
+            None => Vec::new(),
+        };
+
+        if let Some(json) = &mut self.json {
+            serde_json::to_writer(&mut *json, message)?;
+            json.write_all(b"\n")?;
+            json.flush()?;
+        }
+
+        if let Some(csv) = &mut self.csv {
+            let attachments = message.attachments.iter()
+                .map(|a| a.url.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            csv.write_record([
+                message.id.as_str(),
+                &message.timestamp.to_rfc3339(),
+                &self.channel_id,
+                &message.author.id,
+                &message.content,
+                &attachments,
+                &message.embeds.len().to_string(),
+                message.reply_to().unwrap_or(""),
+            ])?;
+            csv.flush()?;
+        }
+
+        if let Some(html) = &mut self.html {
+            writeln!(html, "<div class=\"message\" id=\"m{}\">", message.id)?;
+            writeln!(
+                html,
+                "<div class=\"meta\">{} &middot; {} &middot; {}</div>",
+                escape_html(message.author.display_name()),
+                message.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                message.id,
+            )?;
+            if let Some(reply_to) = message.reply_to() {
+                writeln!(html, "<div class=\"meta\">Reply to {}</div>", escape_html(reply_to))?;
+            }
+            writeln!(html, "<div class=\"content\">{}</div>", escape_html(&message.content))?;
+            for attachment in &message.attachments {
+                let local_path = downloads.iter()
+                    .find(|d| d.attachment_id == attachment.id && d.status == DownloadStatus::Downloaded)
+                    .and_then(|d| d.path.as_deref());
+                writeln!(
+                    html,
+                    "<div><a href=\"{}\">{}</a></div>",
+                    escape_html(local_path.unwrap_or(&attachment.url)),
+                    escape_html(&attachment.filename),
+                )?;
+            }
+            if !message.embeds.is_empty() {
+                writeln!(html, "<div class=\"meta\">{} embed(s)</div>", message.embeds.len())?;
+            }
+            writeln!(html, "</div>")?;
+            html.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,211 @@
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+use crate::{
+    error::{Error, IoContext, Result},
+    transport::Transport,
+    Attachment, Message,
+};
+
+const ATTACHMENTS_DIR: &str = "attachments";
+const MANIFEST_FILE: &str = "manifest.jsonl";
+
+#[derive(Debug, Clone)]
+pub struct AttachmentOptions {
+    /// Attachments larger than this many bytes are recorded in the manifest but not downloaded.
+    pub max_size: u64,
+    /// Number of attachments downloaded at the same time.
+    pub concurrency: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Downloaded,
+    SkippedTooLarge,
+}
+
+/// One line of `manifest.jsonl`, linking a downloaded file to its message.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub message_id: String,
+    pub attachment_id: String,
+    pub filename: String,
+    pub url: String,
+    /// Size reported by Discord, or its `Content-Length` or the bytes downloaded when it was not
+    /// known; `None` when a download without either was stopped at the size limit.
+    pub size: Option<u64>,
+    pub status: DownloadStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Downloads message attachments into `<archive>/<channel>/attachments/`.
+pub struct AttachmentDownloader {
+    transport: Transport,
+    dir: PathBuf,
+    options: AttachmentOptions,
+    manifest: BufWriter<File>,
+}
+
+impl AttachmentDownloader {
+    /// Opens the attachment directory of an archive; files are fetched through `transport`.
+    pub fn open(channel_dir: &Path, options: AttachmentOptions, transport: Transport) -> Result<Self> {
+        let dir = channel_dir.join(ATTACHMENTS_DIR);
+        fs::create_dir_all(&dir)
+            .io_context(|| format!("Failed to create attachment directory {}", dir.display()))?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&manifest_path)
+            .io_context(|| format!("Failed to open attachment manifest {}", manifest_path.display()))?;
+
+        Ok(Self {
+            transport,
+            dir,
+            options,
+            manifest: BufWriter::new(manifest),
+        })
+    }
+
+    /// Downloads every attachment of a message and records it in the manifest.
+    ///
+    /// Returns an error if any download fails, so the caller can keep the message instead of
+    /// deleting it without its files.
+    pub async fn download(&mut self, message: &Message) -> Result<Vec<ManifestEntry>> {
+        let downloads: Vec<_> = message.attachments.iter()
+            .map(|attachment| self.download_one(&message.id, attachment))
+            .collect();
+        let results: Vec<Result<ManifestEntry>> = stream::iter(downloads)
+            .buffered(self.options.concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut entries = Vec::with_capacity(results.len());
+        for result in results {
+            entries.push(result?);
+        }
+
+        for entry in &entries {
+            serde_json::to_writer(&mut self.manifest, entry)?;
+            self.manifest.write_all(b"\n")?;
+        }
+        self.manifest.flush()?;
+
+        Ok(entries)
+    }
+
+    async fn download_one(&self, message_id: &str, attachment: &Attachment) -> Result<ManifestEntry> {
+        let mut entry = ManifestEntry {
+            message_id: message_id.to_string(),
+            attachment_id: attachment.id.clone(),
+            filename: attachment.filename.clone(),
+            url: attachment.url.clone(),
+            size: attachment.size,
+            status: DownloadStatus::SkippedTooLarge,
+            path: None,
+            sha256: None,
+        };
+
+        if let Some(size) = attachment.size.filter(|&size| size > self.options.max_size) {
+            info!(filename = %attachment.filename, size, "Skipping attachment over the size limit");
+            return Ok(entry);
+        }
+
+        let name = format!("{}_{}_{}", message_id, attachment.id, sanitize_filename(&attachment.filename));
+        let path = self.dir.join(&name);
+
+        // CDN URLs are signed and need no credentials, so the token is never sent with them.
+        let response = self.transport.get(&attachment.url).await?;
+        if !response.status().is_success() {
+            return Err(Error::Download(format!("{} returned {}", attachment.url, response.status())));
+        }
+
+        // The size was not known up front, so the limit applies to the announced length and then
+        // to what arrives.
+        if attachment.size.is_none() {
+            entry.size = response.content_length();
+            if let Some(size) = entry.size.filter(|&size| size > self.options.max_size) {
+                info!(filename = %attachment.filename, size, "Skipping attachment over the size limit");
+                return Ok(entry);
+            }
+        }
+
+        let tmp_path = path.with_extension("part");
+        let (downloaded, checksum) = match receive(response, &tmp_path, attachment, self.options.max_size).await {
+            Ok(Some(received)) => received,
+            Ok(None) => {
+                info!(filename = %attachment.filename, "Stopped downloading an attachment over the size limit");
+                let _ = fs::remove_file(&tmp_path);
+                return Ok(entry);
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+        entry.size = Some(downloaded);
+        fs::rename(&tmp_path, &path)?;
+
+        entry.status = DownloadStatus::Downloaded;
+        entry.path = Some(format!("{}/{}", ATTACHMENTS_DIR, name));
+        entry.sha256 = Some(checksum);
+        Ok(entry)
+    }
+}
+
+/// Streams the body of `response` into `path`, hashing it on the way.
+///
+/// Returns the number of bytes received and their SHA-256, `None` as soon as more than
+/// `max_size` bytes arrived, or an error when the count differs from the size Discord or the
+/// `Content-Length` header announced.
+async fn receive(
+    mut response: reqwest::Response,
+    path: &Path,
+    attachment: &Attachment,
+    max_size: u64,
+) -> Result<Option<(u64, String)>> {
+    let expected = attachment.size.or(response.content_length());
+    let mut file = BufWriter::new(
+        File::create(path).io_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+
+    while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .io_context(|| format!("Failed to write {}", path.display()))?;
+        downloaded += chunk.len() as u64;
+        if downloaded > max_size {
+            return Ok(None);
+        }
+    }
+    file.flush()
+        .io_context(|| format!("Failed to write {}", path.display()))?;
+
+    match expected {
+        Some(size) if size != downloaded => Err(Error::Download(format!(
+            "got {} bytes for {} but {} were announced",
+            downloaded, attachment.filename, size
+        ))),
+        _ => Ok(Some((downloaded, hex::encode(hasher.finalize())))),
+    }
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,262 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use std::{
+    collections::HashSet,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::error::{Error, Result};
+
+/// Default audit database, relative to the working directory.
+pub const DEFAULT_AUDIT_DB: &str = "deletions.db";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    description TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS deletions (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    channel_id TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    message_timestamp TEXT,
+    outcome TEXT NOT NULL,
+    http_status INTEGER,
+    error TEXT,
+    recorded_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS deletions_message ON deletions(message_id);
+CREATE INDEX IF NOT EXISTS deletions_channel ON deletions(channel_id, outcome);
+";
+
+/// What happened to one message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Deleted,
+    /// Discord reported the message as unknown, so it was gone before we got to it.
+    AlreadyDeleted,
+    /// Left alone because of a skippable error, e.g. missing permissions.
+    Skipped,
+    Failed,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Deleted => "deleted",
+            Outcome::AlreadyDeleted => "already_deleted",
+            Outcome::Skipped => "skipped",
+            Outcome::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Outcome::Deleted, Outcome::AlreadyDeleted, Outcome::Skipped, Outcome::Failed]
+            .into_iter()
+            .find(|outcome| outcome.as_str() == value)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One row of the `deletions` table.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub run_id: i64,
+    pub channel_id: String,
+    pub message_id: String,
+    pub message_timestamp: Option<DateTime<Utc>>,
+    pub outcome: String,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// One row of the `runs` table, with its counts per outcome.
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub description: String,
+    pub deleted: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+/// Which records [`AuditLog::records`] returns, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct RecordQuery {
+    pub run_id: Option<i64>,
+    pub channel_id: Option<String>,
+    pub outcome: Option<Outcome>,
+    pub limit: usize,
+}
+
+/// Permanent SQLite record of every message a run deleted, skipped or failed to delete.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    conn: Arc<Mutex<Connection>>,
+}
+
+/// A run in progress; every client of the run shares it.
+#[derive(Debug, Clone)]
+pub struct AuditRun {
+    log: AuditLog,
+    run_id: i64,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    pub fn start_run(&self, description: &str) -> Result<AuditRun> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO runs (started_at, description) VALUES (?1, ?2)",
+            params![Utc::now().to_rfc3339(), description],
+        )?;
+        Ok(AuditRun { log: self.clone(), run_id: conn.last_insert_rowid() })
+    }
+
+    /// IDs of the channel's messages that are known to be gone.
+    pub fn deleted_ids(&self, channel_id: &str) -> Result<HashSet<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT message_id FROM deletions WHERE channel_id = ?1 AND outcome IN (?2, ?3)",
+        )?;
+        let ids = statement
+            .query_map(
+                params![channel_id, Outcome::Deleted.as_str(), Outcome::AlreadyDeleted.as_str()],
+                |row| row.get(0),
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(ids)
+    }
+
+    /// Most recent runs first.
+    pub fn runs(&self, limit: usize) -> Result<Vec<RunSummary>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT r.id, r.started_at, r.finished_at, r.description,
+                    COUNT(CASE WHEN d.outcome IN ('deleted', 'already_deleted') THEN 1 END),
+                    COUNT(CASE WHEN d.outcome = 'failed' THEN 1 END),
+                    COUNT(CASE WHEN d.outcome = 'skipped' THEN 1 END)
+             FROM runs r LEFT JOIN deletions d ON d.run_id = r.id
+             GROUP BY r.id ORDER BY r.id DESC LIMIT ?1",
+        )?;
+        let runs = statement
+            .query_map(params![limit as i64], |row| {
+                Ok(RunSummary {
+                    id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    finished_at: row.get(2)?,
+                    description: row.get(3)?,
+                    deleted: row.get(4)?,
+                    failed: row.get(5)?,
+                    skipped: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(runs)
+    }
+
+    pub fn records(&self, query: &RecordQuery) -> Result<Vec<Record>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT run_id, channel_id, message_id, message_timestamp, outcome, http_status, error, recorded_at
+             FROM deletions
+             WHERE (?1 IS NULL OR run_id = ?1)
+               AND (?2 IS NULL OR channel_id = ?2)
+               AND (?3 IS NULL OR outcome = ?3)
+             ORDER BY id DESC LIMIT ?4",
+        )?;
+        let records = statement
+            .query_map(
+                params![
+                    query.run_id,
+                    query.channel_id,
+                    query.outcome.map(Outcome::as_str),
+                    query.limit as i64,
+                ],
+                |row| {
+                    Ok(Record {
+                        run_id: row.get(0)?,
+                        channel_id: row.get(1)?,
+                        message_id: row.get(2)?,
+                        message_timestamp: row.get(3)?,
+                        outcome: row.get(4)?,
+                        http_status: row.get(5)?,
+                        error: row.get(6)?,
+                        recorded_at: row.get(7)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(records)
+    }
+}
+
+impl AuditRun {
+    pub fn id(&self) -> i64 {
+        self.run_id
+    }
+
+    pub fn log(&self) -> &AuditLog {
+        &self.log
+    }
+
+    pub fn record(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        message_timestamp: Option<DateTime<Utc>>,
+        outcome: Outcome,
+        error: Option<&Error>,
+    ) -> Result<()> {
+        let http_status = match (outcome, error) {
+            (Outcome::Deleted, _) => Some(204),
+            (_, Some(error)) => error.status().map(|status| status.as_u16()),
+            (_, None) => None,
+        };
+
+        let conn = self.log.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO deletions
+                (run_id, channel_id, message_id, message_timestamp, outcome, http_status, error, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                self.run_id,
+                channel_id,
+                message_id,
+                message_timestamp.map(|t| t.to_rfc3339()),
+                outcome.as_str(),
+                http_status,
+                error.map(|e| e.to_string()),
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish(&self) -> Result<()> {
+        let conn = self.log.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE runs SET finished_at = ?1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), self.run_id],
+        )?;
+        Ok(())
+    }
+}
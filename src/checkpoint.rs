@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs, path::{Path, PathBuf}};
+
+use crate::error::{Error, IoContext, Result};
+
+/// Default directory for checkpoint files, relative to the working directory.
+pub const DEFAULT_CHECKPOINT_DIR: &str = "checkpoints";
+
+/// Progress of a deletion run, saved after every batch so an interrupted run can be resumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub channel_id: String,
+    pub author_id: String,
+    /// Fingerprint of the date range, listing strategy and filters the cursor was made with;
+    /// a run with other options must not resume from it.
+    #[serde(default)]
+    pub selection: Option<String>,
+    pub last_message_id: Option<String>,
+    #[serde(default)]
+    pub search_offset: usize,
+    pub seen_message_ids: HashSet<String>,
+    pub total_deleted: usize,
+    pub total_failed: usize,
+    pub total_batches: usize,
+    pub total_found: usize,
+    pub completed: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Checkpoint {
+    pub fn new(channel_id: &str, author_id: &str, selection: &str) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+            author_id: author_id.to_string(),
+            selection: Some(selection.to_string()),
+            last_message_id: None,
+            search_offset: 0,
+            seen_message_ids: HashSet::new(),
+            total_deleted: 0,
+            total_failed: 0,
+            total_batches: 0,
+            total_found: 0,
+            completed: false,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn path(dir: &Path, channel_id: &str, author_id: &str) -> PathBuf {
+        dir.join(format!("{}-{}.json", channel_id, author_id))
+    }
+
+    /// Loads a checkpoint, returning `None` if no file exists yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read checkpoint {}", path.display()))?;
+        let checkpoint = serde_json::from_str(&data)
+            .map_err(|source| Error::InvalidCheckpoint { path: path.to_path_buf(), source })?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Writes the checkpoint to a temporary file first so a crash never leaves a truncated file behind.
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .io_context(|| format!("Failed to create checkpoint directory {}", dir.display()))?;
+        }
+
+        self.updated_at = Utc::now();
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)
+            .io_context(|| format!("Failed to write checkpoint {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .io_context(|| format!("Failed to save checkpoint {}", path.display()))?;
+        Ok(())
+    }
+}
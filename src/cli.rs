@@ -0,0 +1,464 @@
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::{path::PathBuf, time::Duration};
+
+use discord_message_deleter::{
+    archive::ArchiveFormat, audit::{Outcome, DEFAULT_AUDIT_DB}, checkpoint::DEFAULT_CHECKPOINT_DIR, retry::DEFAULT_RETRY_FILE, filter::MessageFilter, search::ListingStrategy, snowflake::parse_date,
+    transport::{HttpOptions, DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT, DEFAULT_TRANSPORT_RETRIES},
+    DISCORD_API, MAX_DELETE_DELAY, MESSAGES_PER_REQUEST, MIN_DELETE_DELAY,
+};
+
+/// Exit code used when the run finished but some messages could not be deleted.
+pub const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Exit code used when the run could not be completed at all.
+pub const EXIT_FATAL: u8 = 1;
+
+#[derive(Debug, Parser)]
+#[command(name = "discord_message_deleter", version, about)]
+pub struct Cli {
+    /// Discord user token (defaults to DISCORD_TOKEN, then to the token saved by `login`)
+    #[arg(long, env = "DISCORD_TOKEN", hide_env_values = true, global = true)]
+    pub token: Option<String>,
+
+    /// Passphrase-encrypted token file used by `login` when no keyring is available
+    /// (defaults to token.enc in the user's config directory)
+    #[arg(long, value_name = "FILE", env = "DISCORD_TOKEN_FILE", global = true)]
+    pub token_file: Option<PathBuf>,
+
+    /// Also read DISCORD_TOKEN, AUTHOR_ID and the other variables from a .env file
+    #[arg(long, global = true)]
+    pub dotenv: bool,
+
+    /// Base URL of the Discord API, e.g. a proxy or a mock server for testing
+    #[arg(long, value_name = "URL", env = "DISCORD_API_URL", default_value = DISCORD_API, global = true)]
+    pub api_url: String,
+
+    /// Only log warnings and errors, and print nothing but the final summary
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log more detail: -v adds every API request, -vv everything
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Also write every log event as one JSON object per line to this file
+    #[arg(long, value_name = "FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// TOML file with named profiles of settings (defaults to discord_message_deleter.toml if it exists)
+    #[arg(long, value_name = "FILE", env = "DISCORD_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Profile of the config file to use; flags and environment variables override its values
+    #[arg(short, long, value_name = "NAME", env = "DISCORD_PROFILE", global = true)]
+    pub profile: Option<String>,
+
+    #[command(flatten)]
+    pub http: HttpArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Timeouts, retries and proxy of the connection to Discord.
+#[derive(Debug, Args)]
+#[command(next_help_heading = "HTTP")]
+pub struct HttpArgs {
+    /// Seconds to wait for a connection to be established
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONNECT_TIMEOUT.as_secs(), value_parser = clap::value_parser!(u64).range(1..=300), global = true)]
+    pub connect_timeout: u64,
+
+    /// Seconds an API request may take, including reading the response (attachment downloads
+    /// are not limited)
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_TIMEOUT.as_secs(), value_parser = clap::value_parser!(u64).range(1..=600), global = true)]
+    pub timeout: u64,
+
+    /// Retries of requests that got no response, such as connection errors and timeouts (a
+    /// deletion that timed out is not sent again)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_TRANSPORT_RETRIES, value_parser = clap::value_parser!(u32).range(0..=10), global = true)]
+    pub http_retries: u32,
+
+    /// HTTP, HTTPS or SOCKS5 proxy for every request, e.g. socks5://127.0.0.1:9050
+    #[arg(long, value_name = "URL", env = "DISCORD_PROXY", global = true)]
+    pub proxy: Option<String>,
+
+    /// PEM file of extra CA certificates to trust, e.g. for a TLS-intercepting proxy
+    #[arg(long, value_name = "FILE", env = "DISCORD_CA_BUNDLE", global = true)]
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl HttpArgs {
+    pub fn options(&self) -> HttpOptions {
+        HttpOptions {
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            timeout: Duration::from_secs(self.timeout),
+            retries: self.http_retries,
+            proxy: self.proxy.clone(),
+            ca_bundle: self.ca_bundle.clone(),
+            ..HttpOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Delete your messages from one or more channels without prompting for input
+    Delete(Box<DeleteArgs>),
+    /// Show channel information without deleting anything
+    Info {
+        /// Channel or DM ID to inspect
+        #[arg(value_name = "CHANNEL_ID")]
+        channel: String,
+    },
+    /// Show past runs and the messages they deleted, from the audit database
+    History(HistoryArgs),
+    /// Try again to delete the messages a previous run saved to its retry queue
+    RetryFailed(RetryArgs),
+    /// Inspect the settings resolved from flags, environment variables and the config profile
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Store your token in the OS keyring, or in a passphrase-encrypted file without one
+    Login(LoginArgs),
+    /// Delete the token stored by `login`
+    Logout,
+}
+
+#[derive(Debug, Args)]
+pub struct LoginArgs {
+    /// Store the token in the encrypted file even if a keyring is available
+    #[arg(long)]
+    pub file: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the settings `delete` would use with the same options, and where each comes from
+    Show(Box<DeleteArgs>),
+}
+
+#[derive(Debug, Args)]
+pub struct RetryArgs {
+    #[command(flatten)]
+    pub retry: RetryQueueArgs,
+
+    /// SQLite database recording every deleted message
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_AUDIT_DB)]
+    pub audit_db: PathBuf,
+
+    /// Do not record deletions in the audit database
+    #[arg(long)]
+    pub no_audit: bool,
+}
+
+/// Where failed deletions are kept and how often they are retried.
+#[derive(Debug, Args)]
+pub struct RetryQueueArgs {
+    /// File that messages still failing at the end of the run are saved to
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_RETRY_FILE)]
+    pub retry_file: PathBuf,
+
+    /// Rounds of retries for failed deletions before giving up on them
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(0..=10))]
+    pub retry_rounds: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// Audit database to read
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_AUDIT_DB)]
+    pub db: PathBuf,
+
+    /// List runs with their counts instead of single messages
+    #[arg(long, conflicts_with_all = ["channel", "outcome", "run"])]
+    pub runs: bool,
+
+    /// Only show messages of this run
+    #[arg(long, value_name = "RUN_ID")]
+    pub run: Option<i64>,
+
+    /// Only show messages of this channel
+    #[arg(short, long, value_name = "CHANNEL_ID")]
+    pub channel: Option<String>,
+
+    /// Only show messages with this outcome (deleted, already_deleted, skipped, failed)
+    #[arg(long, value_parser = parse_outcome)]
+    pub outcome: Option<Outcome>,
+
+    /// Maximum number of rows to show
+    #[arg(short, long, value_name = "N", default_value_t = 50)]
+    pub limit: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct DeleteArgs {
+    /// Channel or DM ID to clean up (can be repeated)
+    #[arg(short, long = "channel", value_name = "CHANNEL_ID")]
+    pub channels: Vec<String>,
+
+    /// Clean every text channel, thread and forum post of this server (can be repeated)
+    #[arg(short, long = "guild", value_name = "GUILD_ID")]
+    pub guilds: Vec<String>,
+
+    /// Delete the messages listed in an extracted Discord data package instead of searching
+    /// channels (--channel limits it to those channels)
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["guilds", "from_ids", "strategy", "resume"])]
+    pub from_package: Option<PathBuf>,
+
+    /// Delete the messages of a file with one "CHANNEL_ID,MESSAGE_ID" pair or message link per line
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["guilds", "strategy", "resume"])]
+    pub from_ids: Option<PathBuf>,
+
+    /// Extra pause between message deletions in milliseconds (rate limits are handled automatically)
+    #[arg(
+        short,
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u64).range(MIN_DELETE_DELAY..=MAX_DELETE_DELAY)
+    )]
+    pub delay: u64,
+
+    /// Adjust the pause between deletions automatically from Discord's rate-limit responses
+    #[arg(long, conflicts_with = "delay")]
+    pub adaptive: bool,
+
+    /// Only delete messages from this user ID (defaults to AUTHOR_ID, then to the owner of the token)
+    #[arg(short, long, value_name = "USER_ID", env = "AUTHOR_ID")]
+    pub author: Option<String>,
+
+    /// Only delete messages sent before this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub before: Option<DateTime<Utc>>,
+
+    /// Only delete messages sent on or after this date (YYYY-MM-DD or RFC 3339)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub after: Option<DateTime<Utc>>,
+
+    /// Number of channels processed at the same time
+    #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=16))]
+    pub workers: u16,
+
+    /// Request budget shared by all workers, in requests per second (0 disables it)
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub max_requests_per_second: u32,
+
+    /// Messages fetched per history request
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = MESSAGES_PER_REQUEST,
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(MESSAGES_PER_REQUEST))
+    )]
+    pub page_size: u32,
+
+    /// How to find messages: page through the history or use Discord search
+    #[arg(long, value_enum, default_value_t = StrategyArg::History)]
+    pub strategy: StrategyArg,
+
+    /// Search for messages without deleting them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Write the messages found by --dry-run to a tab-separated file
+    #[arg(short, long, value_name = "FILE", requires = "dry_run")]
+    pub output: Option<PathBuf>,
+
+    /// Save every matched message to this directory before deleting it
+    #[arg(long, value_name = "DIR", conflicts_with = "dry_run")]
+    pub archive: Option<PathBuf>,
+
+    /// Archive formats to write (comma separated)
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [ArchiveFormatArg::Json, ArchiveFormatArg::Csv, ArchiveFormatArg::Html]
+    )]
+    pub archive_format: Vec<ArchiveFormatArg>,
+
+    /// Also download attachments into the archive before deleting their messages
+    #[arg(long, requires = "archive")]
+    pub download_attachments: bool,
+
+    /// Largest attachment to download, in megabytes (bigger ones are only listed in the manifest)
+    #[arg(long, value_name = "MB", default_value_t = 100)]
+    pub max_attachment_size: u64,
+
+    /// Number of attachments downloaded at the same time
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..=32))]
+    pub attachment_concurrency: u16,
+
+    /// Only archive messages, do not delete anything
+    #[arg(long, requires = "archive")]
+    pub export_only: bool,
+
+    /// Continue from the checkpoint of a previous interrupted run
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Directory where progress checkpoints are stored
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_CHECKPOINT_DIR)]
+    pub checkpoint_dir: PathBuf,
+
+    /// SQLite database recording every deleted message; messages listed there are not tried again
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_AUDIT_DB)]
+    pub audit_db: PathBuf,
+
+    /// Do not record deletions in the audit database
+    #[arg(long)]
+    pub no_audit: bool,
+
+    #[command(flatten)]
+    pub retry: RetryQueueArgs,
+
+    /// Answer "yes" to every confirmation prompt
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Show a live dashboard with progress, rate and log instead of log lines (implies --yes)
+    #[arg(long)]
+    pub tui: bool,
+
+    #[command(flatten)]
+    pub filters: FilterArgs,
+}
+
+/// [`ListingStrategy`] as a command-line value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StrategyArg {
+    /// Page through the whole channel history, 100 messages at a time
+    History,
+    /// Ask Discord's search index for messages by the author
+    Search,
+}
+
+impl From<StrategyArg> for ListingStrategy {
+    fn from(strategy: StrategyArg) -> Self {
+        match strategy {
+            StrategyArg::History => ListingStrategy::History,
+            StrategyArg::Search => ListingStrategy::Search,
+        }
+    }
+}
+
+/// [`ArchiveFormat`] as a command-line value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormatArg {
+    /// Full message objects, one JSON document per line
+    Json,
+    /// One flat row per message
+    Csv,
+    /// Readable static transcript
+    Html,
+}
+
+impl From<ArchiveFormatArg> for ArchiveFormat {
+    fn from(format: ArchiveFormatArg) -> Self {
+        match format {
+            ArchiveFormatArg::Json => ArchiveFormat::Json,
+            ArchiveFormatArg::Csv => ArchiveFormat::Csv,
+            ArchiveFormatArg::Html => ArchiveFormat::Html,
+        }
+    }
+}
+
+/// Content filters. Every option given must match for a message to be deleted.
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Filters")]
+pub struct FilterArgs {
+    /// Only delete messages whose content matches this regex (can be repeated, any may match)
+    #[arg(long = "match", value_name = "REGEX", value_parser = parse_regex)]
+    pub matches: Vec<Regex>,
+
+    /// Keep messages whose content matches this regex (can be repeated)
+    #[arg(long = "exclude-match", value_name = "REGEX", value_parser = parse_regex)]
+    pub exclude_matches: Vec<Regex>,
+
+    /// Only delete messages containing one of these keywords, ignoring case (can be repeated)
+    #[arg(long = "keyword", value_name = "WORD")]
+    pub keywords: Vec<String>,
+
+    /// Keep messages containing any of these keywords, ignoring case (can be repeated)
+    #[arg(long = "exclude-keyword", value_name = "WORD")]
+    pub exclude_keywords: Vec<String>,
+
+    /// Only delete messages with attachments
+    #[arg(long, conflicts_with = "no_attachment")]
+    pub has_attachment: bool,
+
+    /// Only delete messages without attachments
+    #[arg(long)]
+    pub no_attachment: bool,
+
+    /// Only delete messages with embeds
+    #[arg(long, conflicts_with = "no_embed")]
+    pub has_embed: bool,
+
+    /// Only delete messages without embeds
+    #[arg(long)]
+    pub no_embed: bool,
+
+    /// Only delete messages containing a link
+    #[arg(long, conflicts_with = "no_link")]
+    pub has_link: bool,
+
+    /// Only delete messages without links
+    #[arg(long)]
+    pub no_link: bool,
+
+    /// Only delete messages of these Discord message types (can be repeated)
+    #[arg(long = "type", value_name = "TYPE")]
+    pub types: Vec<u8>,
+}
+
+impl FilterArgs {
+    pub fn message_filter(&self) -> MessageFilter {
+        let mut filters = Vec::new();
+
+        if !self.matches.is_empty() {
+            filters.push(MessageFilter::Any(
+                self.matches.iter().cloned().map(MessageFilter::Regex).collect(),
+            ));
+        }
+        for regex in &self.exclude_matches {
+            filters.push(MessageFilter::Regex(regex.clone()).negate());
+        }
+        if !self.keywords.is_empty() {
+            filters.push(MessageFilter::keywords(&self.keywords));
+        }
+        if !self.exclude_keywords.is_empty() {
+            filters.push(MessageFilter::keywords(&self.exclude_keywords).negate());
+        }
+
+        let flags = [
+            (self.has_attachment, self.no_attachment, MessageFilter::HasAttachment),
+            (self.has_embed, self.no_embed, MessageFilter::HasEmbed),
+            (self.has_link, self.no_link, MessageFilter::HasLink),
+        ];
+        for (has, has_not, filter) in flags {
+            if has {
+                filters.push(filter);
+            } else if has_not {
+                filters.push(filter.negate());
+            }
+        }
+
+        if !self.types.is_empty() {
+            filters.push(MessageFilter::Type(self.types.clone()));
+        }
+
+        MessageFilter::All(filters)
+    }
+}
+
+fn parse_regex(input: &str) -> Result<Regex, regex::Error> {
+    Regex::new(input)
+}
+
+fn parse_outcome(input: &str) -> Result<Outcome, String> {
+    Outcome::parse(input).ok_or_else(|| format!("unknown outcome '{}'", input))
+}
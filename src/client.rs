@@ -0,0 +1,1062 @@
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use reqwest::{header::HeaderValue, Method, Response, StatusCode};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, task::JoinHandle, time::sleep};
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
+
+use crate::{
+    archive::{Archive, ArchiveFormat},
+    attachments::AttachmentOptions,
+    audit::{AuditRun, Outcome},
+    checkpoint::Checkpoint,
+    control::RunControl,
+    error::{Error, Result, Severity},
+    filter::MessageFilter,
+    pacing::AdaptivePacer,
+    progress::{Progress, ProgressCallback, Prompt, StatsSnapshot},
+    ratelimit::{self, RateLimiter},
+    retry::{Deferred, FailedDeletion},
+    search::ListingStrategy,
+    snowflake::{snowflake_from_timestamp, validate_snowflake},
+    transport::Transport,
+    ChannelInfo, CurrentUser, Message, DISCORD_API, MAX_DELETE_DELAY, MESSAGES_PER_REQUEST, MIN_DELETE_DELAY, PREVIEW_LENGTH,
+};
+
+/// Attempts per request before a retryable error is given up on.
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF_SECONDS: f64 = 1.0;
+const MAX_BACKOFF_SECONDS: f64 = 30.0;
+
+/// Decodes a response body. A body that is not the expected JSON is [`Error::Json`], not a
+/// network error, so it is not retried.
+pub(crate) async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let body = response.text().await?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+pub(crate) struct Stats {
+    pub(crate) total_deleted: usize,
+    pub(crate) total_failed: usize,
+    pub(crate) total_skipped: usize,
+    pub(crate) total_system: usize,
+    pub(crate) total_matched: usize,
+    pub(crate) total_archived: usize,
+    pub(crate) start_time: Instant,
+    pub(crate) messages_in_process: usize,
+}
+
+/// What a run deletes and how.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Extra pause between deletions in milliseconds, ignored when `adaptive` is set.
+    pub delete_delay: u64,
+    pub adaptive: bool,
+    pub dry_run: bool,
+    /// Directory for checkpoint files; `None` disables checkpoints.
+    pub checkpoint_dir: Option<PathBuf>,
+    pub resume: bool,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+    pub filter: MessageFilter,
+    pub strategy: ListingStrategy,
+    /// Directory messages are archived to before they are deleted; `None` disables archiving.
+    pub archive_dir: Option<PathBuf>,
+    pub archive_formats: Vec<ArchiveFormat>,
+    pub attachments: Option<AttachmentOptions>,
+    /// Archive messages without deleting them.
+    pub export_only: bool,
+    /// Rounds of retries for failed deletions at the end of the run; 0 gives up right away.
+    pub retry_rounds: u32,
+    /// Messages fetched per history request, at most [`MESSAGES_PER_REQUEST`].
+    pub page_size: u32,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            delete_delay: 0,
+            adaptive: false,
+            dry_run: false,
+            checkpoint_dir: None,
+            resume: false,
+            before: None,
+            after: None,
+            filter: MessageFilter::default(),
+            strategy: ListingStrategy::default(),
+            archive_dir: None,
+            archive_formats: vec![ArchiveFormat::Json, ArchiveFormat::Csv, ArchiveFormat::Html],
+            attachments: None,
+            export_only: false,
+            retry_rounds: 3,
+            page_size: MESSAGES_PER_REQUEST,
+        }
+    }
+}
+
+/// Builds a [`DiscordClient`] for one channel.
+pub struct ClientBuilder {
+    token: String,
+    channel_id: Option<String>,
+    author_id: Option<String>,
+    guild_id: Option<String>,
+    options: RunOptions,
+    progress: Option<ProgressCallback>,
+    prompt: Option<Prompt>,
+    audit: Option<AuditRun>,
+    known_messages: Option<Vec<Message>>,
+    control: Option<RunControl>,
+    api_base: String,
+    transport: Option<Transport>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ClientBuilder {
+    /// Channel or DM whose messages are listed and deleted. Required.
+    pub fn channel(mut self, channel_id: impl Into<String>) -> Self {
+        self.channel_id = Some(channel_id.into());
+        self
+    }
+
+    /// Only messages of this user are listed. Required.
+    pub fn author(mut self, author_id: impl Into<String>) -> Self {
+        self.author_id = Some(author_id.into());
+        self
+    }
+
+    /// Guild of the channel, which the search strategy needs for server channels.
+    pub fn guild(mut self, guild_id: impl Into<String>) -> Self {
+        self.guild_id = Some(guild_id.into());
+        self
+    }
+
+    pub fn options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn on_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Shares one progress callback between several clients.
+    pub fn progress_callback(mut self, callback: ProgressCallback) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+
+    /// Asks the user before searching further back after long runs without matches.
+    pub fn prompt(mut self, prompt: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.prompt = Some(Arc::new(prompt));
+        self
+    }
+
+    /// Records every deletion in the audit log and skips messages it already lists as deleted.
+    pub fn audit(mut self, run: AuditRun) -> Self {
+        self.audit = Some(run);
+        self
+    }
+
+    /// Deletes exactly these messages, e.g. from a data package, instead of listing the channel.
+    /// They still go through the date range, filters and audit log.
+    pub fn known_messages(mut self, messages: Vec<Message>) -> Self {
+        self.known_messages = Some(messages);
+        self
+    }
+
+    /// Lets another task pause, skip or stop the run.
+    pub fn control(mut self, control: RunControl) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// Base URL of the API, e.g. a proxy or a mock server. Defaults to [`DISCORD_API`].
+    pub fn api_base(mut self, url: impl Into<String>) -> Self {
+        self.api_base = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// HTTP transport to send requests through. Defaults to [`Transport::shared`].
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Rate limiter whose buckets requests wait for. Defaults to [`RateLimiter::shared`];
+    /// Discord counts limits per token, so clients using another token need their own.
+    pub fn rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    pub fn build(self) -> Result<DiscordClient> {
+        let channel_id = self.channel_id
+            .ok_or_else(|| Error::InvalidInput("A channel ID is required".to_string()))?;
+        let author_id = self.author_id
+            .ok_or_else(|| Error::InvalidInput("An author ID is required".to_string()))?;
+
+        for (kind, id) in [("channel", Some(&channel_id)), ("author", Some(&author_id)), ("guild", self.guild_id.as_ref())] {
+            if let Some(id) = id.filter(|id| !validate_snowflake(id)) {
+                return Err(Error::InvalidInput(format!("Invalid {} ID format: {}", kind, id)));
+            }
+        }
+
+        HeaderValue::from_str(&self.token)?;
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => Transport::shared()?.clone(),
+        };
+
+        Ok(DiscordClient {
+            transport,
+            token: self.token,
+            api_base: self.api_base,
+            rate_limiter: self.rate_limiter.unwrap_or_else(RateLimiter::shared),
+            pacer: self.options.adaptive
+                .then(|| Arc::new(AdaptivePacer::new(MIN_DELETE_DELAY, MAX_DELETE_DELAY))),
+            channel_id,
+            guild_id: self.guild_id,
+            author_id,
+            stats: Arc::new(Mutex::new(Stats {
+                total_deleted: 0,
+                total_failed: 0,
+                total_skipped: 0,
+                total_system: 0,
+                total_matched: 0,
+                total_archived: 0,
+                start_time: Instant::now(),
+                messages_in_process: 0,
+            })),
+            options: self.options,
+            progress: self.progress,
+            prompt: self.prompt,
+            audit: self.audit,
+            known_messages: self.known_messages.map(Arc::new),
+            generation: self.control.as_ref().map_or(0, RunControl::generation),
+            control: self.control.unwrap_or_default(),
+            deferred: Arc::new(Mutex::new(Vec::new())),
+            failed: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+}
+
+/// Lists and deletes one user's messages in one channel.
+///
+/// Cloning is cheap; clones share the HTTP transport, rate limiter and counters.
+#[derive(Clone)]
+pub struct DiscordClient {
+    pub(crate) transport: Transport,
+    token: String,
+    pub(crate) api_base: String,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+    pub(crate) pacer: Option<Arc<AdaptivePacer>>,
+    pub(crate) channel_id: String,
+    pub(crate) guild_id: Option<String>,
+    pub(crate) author_id: String,
+    pub(crate) stats: Arc<Mutex<Stats>>,
+    pub(crate) options: RunOptions,
+    progress: Option<ProgressCallback>,
+    prompt: Option<Prompt>,
+    audit: Option<AuditRun>,
+    known_messages: Option<Arc<Vec<Message>>>,
+    pub(crate) control: RunControl,
+    /// Skip count of `control` when the client was built.
+    pub(crate) generation: u64,
+    pub(crate) deferred: Arc<Mutex<Vec<Deferred>>>,
+    pub(crate) failed: Arc<Mutex<Vec<FailedDeletion>>>,
+}
+
+impl DiscordClient {
+    pub fn builder(token: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            token: token.into(),
+            channel_id: None,
+            author_id: None,
+            guild_id: None,
+            options: RunOptions::default(),
+            progress: None,
+            prompt: None,
+            audit: None,
+            known_messages: None,
+            control: None,
+            api_base: DISCORD_API.to_string(),
+            transport: None,
+            rate_limiter: None,
+        }
+    }
+
+    /// Returns the account `token` belongs to; a rejected token is [`Error::InvalidToken`].
+    ///
+    /// Like the other checks made before a client is built, this takes the [`Transport`] to send
+    /// through, usually [`Transport::shared`].
+    pub async fn validate_token(transport: &Transport, api_base: &str, token: &str) -> Result<CurrentUser> {
+        let url = format!("{}/users/@me", api_base);
+        let response = Self::send_unbound(transport, token, &url).await?;
+
+        if response.status().is_success() {
+            read_json(response).await
+        } else {
+            Err(Error::from_response(response, &url).await)
+        }
+    }
+
+    /// Whether the token can see the channel. Only "not found" and "no access" answers are
+    /// `false`; any other failure is returned as an error.
+    pub async fn validate_channel(transport: &Transport, api_base: &str, token: &str, channel_id: &str) -> Result<bool> {
+        let url = format!("{}/channels/{}", api_base, channel_id);
+        let response = Self::send_unbound(transport, token, &url).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(Error::from_response(response, &url).await),
+        }
+    }
+
+    pub async fn get_channel_info(transport: &Transport, api_base: &str, token: &str, channel_id: &str) -> Result<ChannelInfo> {
+        let url = format!("{}/channels/{}", api_base, channel_id);
+        let response = Self::send_unbound(transport, token, &url).await?;
+
+        if response.status().is_success() {
+            read_json(response).await
+        } else {
+            Err(Error::from_response(response, &url).await)
+        }
+    }
+
+    /// Sends a GET request for a call made without a client, through the shared rate limiter.
+    async fn send_unbound(transport: &Transport, token: &str, url: &str) -> Result<Response> {
+        ratelimit::send(transport, token, &RateLimiter::shared(), None, Method::GET, url).await
+    }
+
+    pub fn channel_id(&self) -> &str {
+        &self.channel_id
+    }
+
+    pub fn stats(&self) -> StatsSnapshot {
+        let stats = self.stats.lock().unwrap();
+        StatsSnapshot {
+            deleted: stats.total_deleted,
+            failed: stats.total_failed,
+            skipped: stats.total_skipped,
+            system: stats.total_system,
+            matched: stats.total_matched,
+            archived: stats.total_archived,
+            elapsed: stats.start_time.elapsed(),
+        }
+    }
+
+    pub(crate) fn notify(&self, event: Progress) {
+        if let Some(progress) = &self.progress {
+            progress(&event);
+        }
+    }
+
+    /// Asks the prompt, or answers yes when the client runs unattended.
+    fn confirm(&self, question: &str) -> bool {
+        match &self.prompt {
+            Some(prompt) => prompt(question),
+            None => {
+                info!("{} y (unattended)", question.trim_end());
+                true
+            }
+        }
+    }
+
+    fn checkpoint_path(&self) -> Option<PathBuf> {
+        self.options.checkpoint_dir.as_ref()
+            .map(|dir| Checkpoint::path(dir, &self.channel_id, &self.author_id))
+    }
+
+    /// Fingerprint of the options that decide which messages are listed, see
+    /// [`Checkpoint::selection`].
+    fn selection(&self) -> String {
+        let options = &self.options;
+        let description = format!(
+            "{:?}|{:?}|{:?}|{:?}",
+            options.before, options.after, options.strategy, options.filter
+        );
+        hex::encode(Sha256::digest(description.as_bytes()))
+    }
+
+    pub(crate) fn load_checkpoint(&self) -> Result<Checkpoint> {
+        let selection = self.selection();
+        let fresh = Checkpoint::new(&self.channel_id, &self.author_id, &selection);
+        if !self.options.resume {
+            return Ok(fresh);
+        }
+
+        let Some(path) = self.checkpoint_path() else {
+            return Ok(fresh);
+        };
+
+        match Checkpoint::load(&path)? {
+            Some(checkpoint) if !checkpoint.completed && checkpoint.selection.as_deref() != Some(&selection) => {
+                Err(Error::InvalidInput(format!(
+                    "Checkpoint {} was saved with other date or filter options; resuming would skip messages. \
+                     Run without --resume to start over",
+                    path.display()
+                )))
+            }
+            Some(checkpoint) if !checkpoint.completed => {
+                info!(
+                    path = %path.display(),
+                    batches = checkpoint.total_batches,
+                    last_message_id = checkpoint.last_message_id.as_deref().unwrap_or("None"),
+                    "Resuming from checkpoint"
+                );
+                {
+                    let mut stats = self.stats.lock().unwrap();
+                    stats.total_deleted = checkpoint.total_deleted;
+                    stats.total_failed = checkpoint.total_failed;
+                }
+                Ok(checkpoint)
+            }
+            Some(_) => {
+                info!(path = %path.display(), "Checkpoint is already complete, starting a new run");
+                Ok(fresh)
+            }
+            None => {
+                info!(path = %path.display(), "No checkpoint found, starting a new run");
+                Ok(fresh)
+            }
+        }
+    }
+
+    pub(crate) fn save_checkpoint(&self, checkpoint: &mut Checkpoint) -> Result<()> {
+        let Some(path) = self.checkpoint_path() else {
+            return Ok(());
+        };
+
+        {
+            let stats = self.stats.lock().unwrap();
+            checkpoint.total_deleted = stats.total_deleted;
+            checkpoint.total_failed = stats.total_failed;
+        }
+        checkpoint.save(&path)
+    }
+
+    pub(crate) fn in_date_range(&self, message: &Message) -> bool {
+        self.options.before.is_none_or(|before| message.timestamp < before)
+            && self.options.after.is_none_or(|after| message.timestamp >= after)
+    }
+
+    /// Whether the run was skipped or stopped through its [`RunControl`].
+    pub(crate) fn cancelled(&self) -> bool {
+        self.control.is_cancelled(self.generation)
+    }
+
+    /// Waits while the run is paused, and fails once it is skipped or stopped.
+    pub(crate) async fn check_control(&self) -> Result<()> {
+        self.control.wait_while_paused(self.generation).await;
+        if self.cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn wait_for_batch(&self) {
+        loop {
+            let messages_in_process = {
+                let stats = self.stats.lock().unwrap();
+                stats.messages_in_process
+            };
+
+            if messages_in_process == 0 {
+                break;
+            }
+
+            sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Sends an API request through the shared rate limiter. Deletions also feed the adaptive pacer.
+    pub(crate) async fn send(&self, method: Method, url: &str) -> Result<Response> {
+        let pacer = if method == Method::DELETE { self.pacer.as_deref() } else { None };
+        ratelimit::send(&self.transport, &self.token, &self.rate_limiter, pacer, method, url).await
+    }
+
+    /// Sends a request and returns its successful response, retrying rate limits and server
+    /// errors with exponential backoff. Every other error is returned already classified;
+    /// network errors were already retried by the [`Transport`].
+    pub(crate) async fn request(&self, method: Method, url: &str) -> Result<Response> {
+        let mut backoff = INITIAL_BACKOFF_SECONDS;
+        let mut attempt = 1;
+
+        loop {
+            let error = match self.send(method.clone(), url).await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => Error::from_response(response, url).await,
+                Err(e) => e,
+            };
+
+            let retryable = error.severity() == Severity::Retryable && !matches!(error, Error::Network(_));
+            if !retryable || attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+
+            warn!(%error, backoff_seconds = backoff, attempt = attempt + 1, max_attempts = MAX_ATTEMPTS, "Retrying request");
+            sleep(Duration::from_secs_f64(backoff)).await;
+            backoff = f64::min(backoff * 2.0, MAX_BACKOFF_SECONDS);
+            attempt += 1;
+        }
+    }
+
+    pub(crate) fn print_pace(&self) {
+        if let Some(pacer) = &self.pacer {
+            let (delay_ms, per_minute, rate_limits) = pacer.status();
+            info!(
+                delay_ms,
+                per_minute,
+                rate_limits,
+                "Current pace"
+            );
+        }
+    }
+
+    /// Fetches one page of up to `page_size` messages, newest first, older than `before` when given.
+    pub async fn get_messages(&self, before: Option<&str>) -> Result<Vec<Message>> {
+        let mut url = format!(
+            "{}/channels/{}/messages?limit={}",
+            self.api_base, self.channel_id, self.options.page_size.clamp(1, MESSAGES_PER_REQUEST)
+        );
+
+        if let Some(message_id) = before {
+            url.push_str(&format!("&before={}", message_id));
+        }
+
+        debug!(%url, "Fetching messages");
+
+        let response = self.request(Method::GET, &url).await?;
+        let all_messages: Vec<Message> = read_json(response).await?;
+        debug!(count = all_messages.len(), "Messages received from API");
+
+        Ok(all_messages)
+    }
+
+    /// Deletes one message, retrying temporary failures. Counters are left to the caller.
+    pub async fn delete_message(&self, message_id: &str) -> Result<()> {
+        let url = format!(
+            "{}/channels/{}/messages/{}",
+            self.api_base, self.channel_id, message_id
+        );
+
+        self.request(Method::DELETE, &url).await?;
+        Ok(())
+    }
+
+    /// Counts a failed archive or delete according to its severity and records it in the audit
+    /// log. Fatal errors are returned so the run stops.
+    pub(crate) fn record_error(&self, message_id: &str, timestamp: Option<DateTime<Utc>>, error: Error) -> Result<()> {
+        let reason = error.to_string();
+        match error.severity() {
+            Severity::Skippable => {
+                let outcome = match &error {
+                    Error::UnknownMessage { .. } => {
+                        info!("Message not found (already deleted or too old)");
+                        Outcome::AlreadyDeleted
+                    }
+                    Error::MissingAccess { .. } => {
+                        warn!("No permission to delete message");
+                        Outcome::Skipped
+                    }
+                    Error::SystemMessage { kind } => {
+                        info!(kind, "Skipping system message, it cannot be deleted");
+                        Outcome::Skipped
+                    }
+                    _ => {
+                        warn!(%reason, "Skipping message");
+                        Outcome::Skipped
+                    }
+                };
+                self.audit(message_id, timestamp, outcome, Some(&error))?;
+                let message_id = message_id.to_string();
+                match error {
+                    Error::SystemMessage { kind } => {
+                        self.stats.lock().unwrap().total_system += 1;
+                        self.notify(Progress::Undeletable { message_id, kind });
+                    }
+                    _ => {
+                        self.stats.lock().unwrap().total_skipped += 1;
+                        self.notify(Progress::Skipped { message_id, reason });
+                    }
+                }
+                Ok(())
+            }
+            Severity::Retryable => {
+                error!(%reason, "Failed to delete message");
+                self.audit(message_id, timestamp, Outcome::Failed, Some(&error))?;
+                self.stats.lock().unwrap().total_failed += 1;
+                self.notify(Progress::Failed { message_id: message_id.to_string(), reason });
+                Ok(())
+            }
+            Severity::Fatal => {
+                error!(%reason, "Fatal error, stopping");
+                self.audit(message_id, timestamp, Outcome::Failed, Some(&error))?;
+                self.stats.lock().unwrap().total_failed += 1;
+                self.notify(Progress::Failed { message_id: message_id.to_string(), reason });
+                Err(error)
+            }
+        }
+    }
+
+    pub(crate) fn audit(
+        &self,
+        message_id: &str,
+        timestamp: Option<DateTime<Utc>>,
+        outcome: Outcome,
+        error: Option<&Error>,
+    ) -> Result<()> {
+        match &self.audit {
+            Some(run) => run.record(&self.channel_id, message_id, timestamp, outcome, error),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn record_deleted(&self, message_id: &str, timestamp: Option<DateTime<Utc>>) -> Result<()> {
+        self.audit(message_id, timestamp, Outcome::Deleted, None)?;
+        self.stats.lock().unwrap().total_deleted += 1;
+        if let Some(pacer) = &self.pacer {
+            pacer.on_deleted();
+        }
+        info!("Deleted message");
+        self.notify(Progress::Deleted { message_id: message_id.to_string() });
+        Ok(())
+    }
+
+    /// IDs the audit log already knows to be deleted, so re-runs do not try them again.
+    pub(crate) fn known_deleted(&self) -> Result<HashSet<String>> {
+        match &self.audit {
+            Some(run) => run.log().deleted_ids(&self.channel_id),
+            None => Ok(HashSet::new()),
+        }
+    }
+
+    /// Whether a listed message should be handed out: written by the author, inside the date
+    /// range, matching the filters, and not already handled.
+    pub(crate) fn wanted(&self, message: &Message, seen: &HashSet<String>, known_deleted: &HashSet<String>) -> bool {
+        if message.author.id != self.author_id || !self.in_date_range(message) || !self.options.filter.matches(message) {
+            return false;
+        }
+        if seen.contains(&message.id) {
+            debug!(message_id = %message.id, "Skipping already processed message");
+            return false;
+        }
+        if known_deleted.contains(&message.id) {
+            debug!(message_id = %message.id, "Skipping message the audit log lists as deleted");
+            return false;
+        }
+        true
+    }
+
+    fn report_candidate(&self, message: &Message) {
+        info!(
+            timestamp = %message.timestamp.to_rfc3339(),
+            content = %message.preview(PREVIEW_LENGTH),
+            "[dry-run] Would delete message"
+        );
+        self.notify(Progress::WouldDelete(Box::new(message.clone())));
+    }
+
+    fn open_archive(&self) -> Result<Option<Archive>> {
+        match &self.options.archive_dir {
+            Some(dir) => Ok(Some(Archive::open(
+                dir,
+                &self.channel_id,
+                &self.options.archive_formats,
+                self.options.attachments.clone(),
+                &self.transport,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Archives and deletes every received message. Stops at the first fatal error, releasing
+    /// the listing so it does not wait for messages that will never be processed.
+    ///
+    /// Failures that may go away are queued instead of counted. Call
+    /// [`retry_deferred`](Self::retry_deferred) afterwards, or
+    /// [`give_up_deferred`](Self::give_up_deferred) to skip the retries, so they end up in the
+    /// stats and in [`failed_deletions`](Self::failed_deletions).
+    pub async fn process_messages(&self, rx: mpsc::Receiver<Message>, archive: Option<Archive>) -> Result<()> {
+        let result = self.process_each(rx, archive).await;
+        if result.is_err() {
+            self.stats.lock().unwrap().messages_in_process = 0;
+        }
+        result
+    }
+
+    async fn process_each(&self, mut rx: mpsc::Receiver<Message>, mut archive: Option<Archive>) -> Result<()> {
+        while let Some(message) = rx.recv().await {
+            self.check_control().await?;
+            let result = self.process_one(&message, archive.as_mut()).await;
+            {
+                let mut stats = self.stats.lock().unwrap();
+                stats.messages_in_process = stats.messages_in_process.saturating_sub(1);
+            }
+            result?;
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "delete", skip_all, fields(message_id = %message.id))]
+    async fn process_one(&self, message: &Message, archive: Option<&mut Archive>) -> Result<()> {
+        if !message.is_deletable() {
+            let error = Error::SystemMessage { kind: message.type_name() };
+            return self.record_error(&message.id, Some(message.timestamp), error);
+        }
+
+        if self.options.dry_run {
+            self.report_candidate(message);
+            self.stats.lock().unwrap().total_matched += 1;
+            return Ok(());
+        }
+
+        if let Some(archive) = archive {
+            if let Err(e) = archive.write(message).await {
+                error!(error = %e, "Failed to archive message, not deleting it");
+                return self.record_error(&message.id, Some(message.timestamp), e);
+            }
+
+            self.stats.lock().unwrap().total_archived += 1;
+            self.notify(Progress::Archived { message_id: message.id.clone() });
+            if self.options.export_only {
+                info!("Archived message");
+                return Ok(());
+            }
+        }
+
+        match self.delete_message(&message.id).await {
+            Ok(()) => self.record_deleted(&message.id, Some(message.timestamp))?,
+            Err(e) => {
+                if let Some(e) = self.defer(message, e) {
+                    self.record_error(&message.id, Some(message.timestamp), e)?;
+                }
+            }
+        }
+
+        match &self.pacer {
+            Some(pacer) => sleep(pacer.delay()).await,
+            None if self.options.delete_delay > 0 => {
+                sleep(Duration::from_millis(self.options.delete_delay)).await;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Sends every message that should be deleted to `tx`, using the configured strategy or the
+    /// messages given to the builder.
+    ///
+    /// The next page is only fetched once the previous one was processed, so the receiving end
+    /// must be handed to [`process_messages`](Self::process_messages) on this client or a clone.
+    pub async fn list_messages(&self, tx: mpsc::Sender<Message>) -> Result<()> {
+        if let Some(messages) = &self.known_messages {
+            return self.list_known(messages, tx).await;
+        }
+        match self.options.strategy {
+            ListingStrategy::History => self.list_history(tx).await,
+            ListingStrategy::Search => self.list_search(tx).await,
+        }
+    }
+
+    /// Hands out the messages given to the builder, a page at a time, without asking Discord.
+    async fn list_known(&self, messages: &[Message], tx: mpsc::Sender<Message>) -> Result<()> {
+        let known_deleted = self.known_deleted()?;
+        let seen = HashSet::new();
+        let wanted: Vec<_> = messages.iter()
+            .filter(|m| self.wanted(m, &seen, &known_deleted))
+            .cloned()
+            .collect();
+        info!(given = messages.len(), found = wanted.len(), "Using the given message IDs instead of listing the channel");
+
+        let mut total_found = 0;
+        self.notify(Progress::Expected { total: wanted.len() });
+        for (batch, page) in wanted.chunks(self.options.page_size.clamp(1, MESSAGES_PER_REQUEST) as usize).enumerate() {
+            self.wait_for_batch().await;
+            if self.cancelled() {
+                return Ok(());
+            }
+            total_found += page.len();
+            self.notify(Progress::BatchChecked { batch: batch + 1, found: page.len(), total_found });
+            self.stats.lock().unwrap().messages_in_process = page.len();
+
+            for message in page {
+                if tx.send(message.clone()).await.is_err() {
+                    debug!("Receiver has been dropped, stopping message listing");
+                    return Ok(());
+                }
+            }
+        }
+
+        self.wait_for_batch().await;
+        Ok(())
+    }
+
+    /// Lists the author's messages that pass the filters, newest first.
+    ///
+    /// The next page is only fetched once every message of the current one has been taken from
+    /// the stream, so checkpoints never skip a message that was not handed out yet.
+    pub fn messages(&self) -> impl Stream<Item = Result<Message>> + Send + 'static {
+        let (tx, rx) = mpsc::channel(MESSAGES_PER_REQUEST as usize);
+        let client = self.clone();
+        let span = info_span!("channel", channel_id = %self.channel_id);
+        let listing = tokio::spawn(async move { client.list_messages(tx).await }.instrument(span));
+
+        struct State {
+            rx: mpsc::Receiver<Message>,
+            listing: Option<JoinHandle<Result<()>>>,
+            stats: Arc<Mutex<Stats>>,
+        }
+
+        let state = State { rx, listing: Some(listing), stats: Arc::clone(&self.stats) };
+        stream::unfold(state, |mut state| async move {
+            if let Some(message) = state.rx.recv().await {
+                {
+                    let mut stats = state.stats.lock().unwrap();
+                    stats.messages_in_process = stats.messages_in_process.saturating_sub(1);
+                }
+                return Some((Ok(message), state));
+            }
+
+            let result = match state.listing.take()?.await {
+                Ok(result) => result,
+                Err(e) => Err(e.into()),
+            };
+            result.err().map(|e| (Err(e), state))
+        })
+    }
+
+    pub(crate) async fn list_history(&self, tx: mpsc::Sender<Message>) -> Result<()> {
+        let mut checkpoint = self.load_checkpoint()?;
+        let mut last_message_id = checkpoint.last_message_id.clone().or_else(|| {
+            self.options.before.map(|before| snowflake_from_timestamp(before).to_string())
+        });
+        let mut seen_message_ids = checkpoint.seen_message_ids.clone();
+        let mut total_batches = checkpoint.total_batches;
+        let mut consecutive_empty = 0;
+        let mut total_found = checkpoint.total_found;
+        let known_deleted = self.known_deleted()?;
+
+        loop {
+            self.wait_for_batch().await;
+            if self.cancelled() {
+                info!("Stopped, the checkpoint keeps the last completed batch");
+                return Ok(());
+            }
+
+            checkpoint.last_message_id = last_message_id.clone();
+            checkpoint.seen_message_ids = seen_message_ids.clone();
+            checkpoint.total_batches = total_batches;
+            checkpoint.total_found = total_found;
+            self.save_checkpoint(&mut checkpoint)?;
+
+            let all_messages = self.get_messages(last_message_id.as_deref())
+                .instrument(info_span!("batch", batch = total_batches + 1))
+                .await?;
+            total_batches += 1;
+
+            if all_messages.is_empty() {
+                consecutive_empty += 1;
+                info!(attempt = consecutive_empty, "Empty batch (attempt {} of 10)", consecutive_empty);
+
+                if consecutive_empty >= 10 {
+                    info!(
+                        last_message_id = last_message_id.as_deref().unwrap_or("None"),
+                        "No messages found in the last 10 batches"
+                    );
+                    if self.prompt.is_none() {
+                        info!("Reached the beginning of the channel");
+                        break;
+                    }
+                    if !self.confirm("Would you like to continue searching older messages? (Y/n): ") {
+                        info!("Search stopped by user");
+                        break;
+                    } else {
+                        consecutive_empty = 0;
+                        info!("Continuing search");
+                        continue;
+                    }
+                }
+
+                if let Some(last_id) = &last_message_id {
+                    debug!(before = %last_id, "Moving to older messages");
+                    sleep(Duration::from_millis(200)).await;
+                    continue;
+                } else {
+                    info!("No messages found and no message ID to paginate from, stopping");
+                    break;
+                }
+            }
+
+            if let Some(last) = all_messages.last() {
+                last_message_id = Some(last.id.clone());
+            }
+
+            let reached_lower_bound = match (self.options.after, all_messages.last()) {
+                (Some(after), Some(oldest)) => oldest.timestamp < after,
+                _ => false,
+            };
+
+            let user_messages: Vec<_> = all_messages.into_iter()
+                .filter(|m| self.wanted(m, &seen_message_ids, &known_deleted))
+                .collect();
+
+            let batch_user_messages = user_messages.len();
+            total_found += batch_user_messages;
+
+            info!(batch = total_batches, found = batch_user_messages, total_found, "Batch checked");
+            self.notify(Progress::BatchChecked { batch: total_batches, found: batch_user_messages, total_found });
+
+            if batch_user_messages > 0 {
+                consecutive_empty = 0;
+
+                {
+                    let mut stats = self.stats.lock().unwrap();
+                    stats.messages_in_process = batch_user_messages;
+                }
+
+                for message in user_messages {
+                    seen_message_ids.insert(message.id.to_string());
+                    if tx.send(message).await.is_err() {
+                        debug!("Receiver has been dropped, stopping message listing");
+                        return Ok(());
+                    }
+                }
+            } else if !reached_lower_bound {
+                consecutive_empty += 1;
+                info!(attempt = consecutive_empty, "Empty batch (attempt {} of 10)", consecutive_empty);
+
+                if total_batches == 1 {
+                    info!(
+                        "No messages found from you in the first {} messages, searching older ones might take longer",
+                        self.options.page_size
+                    );
+                    if !self.confirm("Continue searching? (Y/n): ") {
+                        return Err(Error::Aborted);
+                    }
+                    consecutive_empty = 0;
+                    continue;
+                }
+
+                if consecutive_empty >= 10 {
+                    info!(
+                        last_message_id = last_message_id.as_deref().unwrap_or("None"),
+                        "No user messages found in the last 10 batches"
+                    );
+                    if !self.confirm("Would you like to continue searching older messages? (Y/n): ") {
+                        info!("Search stopped by user");
+                        break;
+                    } else {
+                        consecutive_empty = 0;
+                        info!("Continuing search");
+                        continue;
+                    }
+                }
+            }
+
+            if reached_lower_bound {
+                info!("Reached messages older than the --after date, stopping search");
+                break;
+            }
+
+            {
+                let stats = self.stats.lock().unwrap();
+                info!(
+                    deleted = stats.total_deleted,
+                    failed = stats.total_failed,
+                    in_process = stats.messages_in_process,
+                    batches = total_batches,
+                    total_found,
+                    "Overall progress"
+                );
+                self.print_pace();
+
+                if stats.total_deleted + stats.total_failed >= total_found &&
+                   consecutive_empty >= 10 &&
+                   total_found > 0 {
+                    info!(
+                        last_message_id = last_message_id.as_deref().unwrap_or("None"),
+                        "All found messages have been processed"
+                    );
+                    if !self.confirm("Would you like to continue searching older messages? (Y/n): ") {
+                        info!("Search stopped by user");
+                        break;
+                    } else {
+                        consecutive_empty = 0;
+                        info!("Continuing search");
+                    }
+                }
+            }
+
+            sleep(Duration::from_millis(200)).await;
+        }
+
+        self.wait_for_batch().await;
+
+        checkpoint.last_message_id = last_message_id;
+        checkpoint.seen_message_ids = seen_message_ids;
+        checkpoint.total_batches = total_batches;
+        checkpoint.total_found = total_found;
+        checkpoint.completed = true;
+        self.save_checkpoint(&mut checkpoint)?;
+
+        Ok(())
+    }
+
+    /// Runs the whole pipeline: lists matching messages, archives them when configured, and
+    /// deletes them (or only reports them, in a dry run).
+    #[instrument(name = "channel", skip_all, fields(channel_id = %self.channel_id))]
+    pub async fn delete_all_messages(&self) -> Result<StatsSnapshot> {
+        let (tx, rx) = mpsc::channel(100);
+        let archive = self.open_archive()?;
+        let archive_dir = archive.as_ref().map(|a| a.dir().to_path_buf());
+
+        let list_client = self.clone();
+        let process_client = self.clone();
+
+        info!(strategy = ?self.options.strategy, dry_run = self.options.dry_run, "Starting message search and deletion");
+
+        let list_handle = tokio::spawn(async move {
+            list_client.list_messages(tx).await
+        }.in_current_span());
+
+        let process_handle = tokio::spawn(async move {
+            process_client.process_messages(rx, archive).await
+        }.in_current_span());
+
+        let listed = list_handle.await?;
+        let processed = process_handle.await?;
+        match processed {
+            Ok(()) => self.retry_deferred().await?,
+            Err(_) => self.give_up_deferred()?,
+        }
+        processed?;
+        listed?;
+
+        let stats = self.stats();
+        info!(
+            deleted = stats.deleted,
+            failed = stats.failed,
+            skipped = stats.skipped,
+            system = stats.system,
+            matched = stats.matched,
+            archived = stats.archived,
+            archive_dir = archive_dir.as_ref().map(|dir| dir.display().to_string()),
+            elapsed_seconds = stats.elapsed.as_secs(),
+            "Channel finished"
+        );
+        self.notify(Progress::Finished(stats));
+
+        Ok(stats)
+    }
+}
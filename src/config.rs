@@ -0,0 +1,651 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use discord_message_deleter::{snowflake::parse_date, MAX_DELETE_DELAY, MESSAGES_PER_REQUEST, MIN_DELETE_DELAY};
+
+use crate::cli::{ArchiveFormatArg, Cli, Command, ConfigCommand, DeleteArgs, StrategyArg};
+
+/// Config file read from the working directory when `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = "discord_message_deleter.toml";
+
+/// Profile used when neither `--profile` nor `default_profile` picks one.
+const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Where a profile's token comes from, e.g. `token = { env = "WORK_TOKEN" }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum TokenSource {
+    /// Name of an environment variable holding the token.
+    Env(String),
+    /// File whose first line is the token.
+    File(PathBuf),
+    /// The token itself; prefer the other sources so it does not end up in backups.
+    Value(String),
+}
+
+impl TokenSource {
+    fn read(&self) -> Result<String> {
+        match self {
+            TokenSource::Env(name) => env::var(name)
+                .with_context(|| format!("Environment variable {} named by the profile's token is not set", name)),
+            TokenSource::File(path) => {
+                let data = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read token file {}", path.display()))?;
+                Ok(data.lines().next().unwrap_or_default().trim().to_string())
+            }
+            TokenSource::Value(token) => Ok(token.clone()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            TokenSource::Env(name) => format!("env {}", name),
+            TokenSource::File(path) => format!("file {}", path.display()),
+            TokenSource::Value(_) => "value".to_string(),
+        }
+    }
+}
+
+/// One named set of settings. Everything is optional; unset values keep their defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub token: Option<TokenSource>,
+    pub author: Option<String>,
+    pub api_url: Option<String>,
+    pub channels: Option<Vec<String>>,
+    pub guilds: Option<Vec<String>>,
+    pub dry_run: Option<bool>,
+    pub filters: FilterSettings,
+    pub pacing: PacingSettings,
+    pub backup: BackupSettings,
+    pub output: OutputSettings,
+    pub http: HttpSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterSettings {
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub matches: Option<Vec<String>>,
+    pub exclude_matches: Option<Vec<String>>,
+    pub keywords: Option<Vec<String>>,
+    pub exclude_keywords: Option<Vec<String>>,
+    pub has_attachment: Option<bool>,
+    pub no_attachment: Option<bool>,
+    pub has_embed: Option<bool>,
+    pub no_embed: Option<bool>,
+    pub has_link: Option<bool>,
+    pub no_link: Option<bool>,
+    pub types: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PacingSettings {
+    pub delay: Option<u64>,
+    pub adaptive: Option<bool>,
+    pub workers: Option<u16>,
+    pub max_requests_per_second: Option<u32>,
+    pub strategy: Option<String>,
+    pub page_size: Option<u32>,
+    pub retry_rounds: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupSettings {
+    pub archive: Option<PathBuf>,
+    pub formats: Option<Vec<String>>,
+    pub download_attachments: Option<bool>,
+    pub max_attachment_size: Option<u64>,
+    pub attachment_concurrency: Option<u16>,
+    pub export_only: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSettings {
+    pub quiet: Option<bool>,
+    pub log_file: Option<PathBuf>,
+    pub dry_run_file: Option<PathBuf>,
+    pub checkpoint_dir: Option<PathBuf>,
+    pub audit_db: Option<PathBuf>,
+    pub no_audit: Option<bool>,
+    pub retry_file: Option<PathBuf>,
+    pub tui: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSettings {
+    pub connect_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+    pub proxy: Option<String>,
+    pub ca_bundle: Option<PathBuf>,
+}
+
+/// The profile picked for this run, empty when there is no config file.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub path: Option<PathBuf>,
+    pub name: Option<String>,
+    pub profile: Profile,
+}
+
+impl Config {
+    /// Reads the config file and picks the profile: `profile`, else the file's
+    /// `default_profile`, else one called "default" if there is one.
+    ///
+    /// Without `path`, [`DEFAULT_CONFIG_FILE`] is used when it exists.
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => PathBuf::from(DEFAULT_CONFIG_FILE),
+            None => {
+                return match profile {
+                    Some(name) => Err(anyhow!("Profile {} requested but {} does not exist", name, DEFAULT_CONFIG_FILE)),
+                    None => Ok(Self::default()),
+                };
+            }
+        };
+
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut file: ConfigFile = toml::from_str(&data)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        let name = profile.map(str::to_string)
+            .or(file.default_profile.take())
+            .or_else(|| file.profiles.contains_key(DEFAULT_PROFILE).then(|| DEFAULT_PROFILE.to_string()));
+        let profile = match &name {
+            Some(name) => file.profiles.remove(name).ok_or_else(|| anyhow!(
+                "No profile {} in {} (available: {})",
+                name,
+                path.display(),
+                file.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))?,
+            None => Profile::default(),
+        };
+
+        Ok(Self { path: Some(path), name, profile })
+    }
+}
+
+/// Where a resolved setting came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Env,
+    Profile,
+    Default,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Source::Flag => "flag",
+            Source::Env => "environment",
+            Source::Profile => "profile",
+            Source::Default => "default",
+        })
+    }
+}
+
+/// One resolved setting, for `config show`.
+#[derive(Debug)]
+pub struct Setting {
+    pub name: &'static str,
+    pub value: String,
+    pub source: Source,
+}
+
+/// Formats a setting's value for `config show`.
+trait Show {
+    fn show(&self) -> String;
+}
+
+macro_rules! show_with_display {
+    ($($type:ty),*) => {
+        $(impl Show for $type {
+            fn show(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+show_with_display!(String, bool, u8, u16, u32, u64);
+
+impl Show for PathBuf {
+    fn show(&self) -> String {
+        self.display().to_string()
+    }
+}
+
+impl Show for Regex {
+    fn show(&self) -> String {
+        self.as_str().to_string()
+    }
+}
+
+impl Show for DateTime<Utc> {
+    fn show(&self) -> String {
+        self.to_rfc3339()
+    }
+}
+
+impl Show for StrategyArg {
+    fn show(&self) -> String {
+        value_name(self)
+    }
+}
+
+impl Show for ArchiveFormatArg {
+    fn show(&self) -> String {
+        value_name(self)
+    }
+}
+
+impl<T: Show> Show for Option<T> {
+    fn show(&self) -> String {
+        self.as_ref().map_or_else(|| "-".to_string(), Show::show)
+    }
+}
+
+impl<T: Show> Show for Vec<T> {
+    fn show(&self) -> String {
+        if self.is_empty() {
+            return "-".to_string();
+        }
+        self.iter().map(Show::show).collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn value_name(value: &impl ValueEnum) -> String {
+    value.to_possible_value().map_or_else(String::new, |v| v.get_name().to_string())
+}
+
+/// Shows the first and last characters of a token only.
+fn mask(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    format!(
+        "{}...{}",
+        chars[..4].iter().collect::<String>(),
+        chars[chars.len() - 4..].iter().collect::<String>()
+    )
+}
+
+/// Applies profile values to the settings of one (sub)command that were not given as flags or
+/// environment variables, and records where every setting came from.
+struct Layer<'a> {
+    matches: &'a ArgMatches,
+    settings: &'a mut Vec<Setting>,
+}
+
+impl Layer<'_> {
+    /// The source of a value that outranks the profile.
+    fn given(&self, id: &str) -> Option<Source> {
+        match self.matches.value_source(id) {
+            Some(ValueSource::CommandLine) => Some(Source::Flag),
+            Some(ValueSource::EnvVariable) => Some(Source::Env),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, name: &'static str, value: String, source: Source) {
+        self.settings.push(Setting { name, value, source });
+    }
+
+    /// Sets `target` to the profile `value` unless the argument `id` was given, and returns
+    /// where the setting came from.
+    fn layer<T: Show>(&mut self, id: &str, name: &'static str, target: &mut T, value: Option<T>) -> Source {
+        let source = match (self.given(id), value) {
+            (Some(source), _) => source,
+            (None, Some(value)) => {
+                *target = value;
+                Source::Profile
+            }
+            (None, None) => Source::Default,
+        };
+        let shown = target.show();
+        self.record(name, shown, source);
+        source
+    }
+}
+
+fn check_range<T: PartialOrd + Display + Copy>(name: &str, value: Option<T>, range: RangeInclusive<T>) -> Result<Option<T>> {
+    match value {
+        Some(v) if !range.contains(&v) => {
+            Err(anyhow!("{} must be between {} and {} in the profile, got {}", name, range.start(), range.end(), v))
+        }
+        _ => Ok(value),
+    }
+}
+
+fn parse_value_enum<T: ValueEnum>(name: &str, value: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|_| anyhow!("Invalid {} in the profile: {}", name, value))
+}
+
+fn parse_regexes(name: &str, patterns: Option<&Vec<String>>) -> Result<Option<Vec<Regex>>> {
+    patterns.map(|patterns| {
+        patterns.iter()
+            .map(|p| Regex::new(p).with_context(|| format!("Invalid regex in {} of the profile", name)))
+            .collect()
+    })
+    .transpose()
+}
+
+fn parse_profile_date(name: &str, value: Option<&String>) -> Result<Option<DateTime<Utc>>> {
+    value.map(|v| parse_date(v).map_err(|e| anyhow!("Invalid {} in the profile: {}", name, e))).transpose()
+}
+
+/// Loads the profile picked by `--config`/`--profile` and applies it to `cli`.
+pub fn resolve(cli: &mut Cli, matches: &ArgMatches) -> Result<(Config, Vec<Setting>)> {
+    let config = Config::load(cli.config.as_deref(), cli.profile.as_deref())?;
+    let settings = apply(&config, cli, matches)?;
+    Ok((config, settings))
+}
+
+/// Fills in everything the command line left open from the profile and returns the resolved
+/// settings. Flags and environment variables win over the profile, the profile over defaults.
+fn apply(config: &Config, cli: &mut Cli, matches: &ArgMatches) -> Result<Vec<Setting>> {
+    let profile = &config.profile;
+    let mut settings = Vec::new();
+    let mut layer = Layer { matches, settings: &mut settings };
+
+    match (layer.given("token"), &profile.token) {
+        (Some(source), _) => layer.record("token", cli.token.as_deref().map_or_else(|| "-".to_string(), mask), source),
+        (None, Some(token)) => {
+            let value = token.read()?;
+            layer.record("token", format!("{} ({})", mask(&value), token.describe()), Source::Profile);
+            cli.token = Some(value);
+        }
+        (None, None) => layer.record("token", "stored by login, if any".to_string(), Source::Default),
+    }
+    layer.layer("api_url", "api_url", &mut cli.api_url, profile.api_url.clone());
+    layer.layer("log_file", "output.log_file", &mut cli.log_file, profile.output.log_file.clone().map(Some));
+    // -v on the command line outranks a quiet profile.
+    let quiet = profile.output.quiet.filter(|_| layer.given("verbose").is_none());
+    layer.layer("quiet", "output.quiet", &mut cli.quiet, quiet);
+
+    let http = &profile.http;
+    let connect_timeout = check_range("http.connect_timeout", http.connect_timeout, 1..=300)?;
+    layer.layer("connect_timeout", "http.connect_timeout", &mut cli.http.connect_timeout, connect_timeout);
+    let timeout = check_range("http.timeout", http.timeout, 1..=600)?;
+    layer.layer("timeout", "http.timeout", &mut cli.http.timeout, timeout);
+    let retries = check_range("http.retries", http.retries, 0..=10)?;
+    layer.layer("http_retries", "http.retries", &mut cli.http.http_retries, retries);
+    layer.layer("proxy", "http.proxy", &mut cli.http.proxy, http.proxy.clone().map(Some));
+    layer.layer("ca_bundle", "http.ca_bundle", &mut cli.http.ca_bundle, http.ca_bundle.clone().map(Some));
+
+    let Some((_, sub_matches)) = matches.subcommand() else {
+        return Ok(settings);
+    };
+    match &mut cli.command {
+        Some(Command::Delete(args)) => apply_delete(profile, args, sub_matches, &mut settings)?,
+        Some(Command::Config { command: ConfigCommand::Show(args) }) => {
+            if let Some((_, show_matches)) = sub_matches.subcommand() {
+                apply_delete(profile, args, show_matches, &mut settings)?;
+            }
+        }
+        Some(Command::History(args)) => {
+            let mut layer = Layer { matches: sub_matches, settings: &mut settings };
+            layer.layer("db", "output.audit_db", &mut args.db, profile.output.audit_db.clone());
+        }
+        Some(Command::RetryFailed(args)) => {
+            let mut layer = Layer { matches: sub_matches, settings: &mut settings };
+            let output = &profile.output;
+            layer.layer("retry_file", "output.retry_file", &mut args.retry.retry_file, output.retry_file.clone());
+            let rounds = check_range("pacing.retry_rounds", profile.pacing.retry_rounds, 0..=10)?;
+            layer.layer("retry_rounds", "pacing.retry_rounds", &mut args.retry.retry_rounds, rounds);
+            layer.layer("audit_db", "output.audit_db", &mut args.audit_db, output.audit_db.clone());
+            layer.layer("no_audit", "output.no_audit", &mut args.no_audit, output.no_audit);
+        }
+        Some(Command::Info { .. } | Command::Login(_) | Command::Logout) | None => {}
+    }
+    Ok(settings)
+}
+
+fn apply_delete(profile: &Profile, args: &mut DeleteArgs, matches: &ArgMatches, settings: &mut Vec<Setting>) -> Result<()> {
+    let mut layer = Layer { matches, settings };
+
+    // Targets replace each other as a whole: any target on the command line ignores the
+    // profile's channels and guilds.
+    let targets_given = ["channels", "guilds", "from_package", "from_ids"].iter().any(|id| layer.given(id).is_some());
+    let (channels, guilds) = match targets_given {
+        true => (None, None),
+        false => (profile.channels.clone(), profile.guilds.clone()),
+    };
+    layer.layer("channels", "channels", &mut args.channels, channels);
+    layer.layer("guilds", "guilds", &mut args.guilds, guilds);
+    layer.layer("author", "author", &mut args.author, profile.author.clone().map(Some));
+    layer.layer("dry_run", "dry_run", &mut args.dry_run, profile.dry_run);
+
+    let filters = &profile.filters;
+    let before = parse_profile_date("filters.before", filters.before.as_ref())?;
+    layer.layer("before", "filters.before", &mut args.before, before.map(Some));
+    let after = parse_profile_date("filters.after", filters.after.as_ref())?;
+    layer.layer("after", "filters.after", &mut args.after, after.map(Some));
+    let f = &mut args.filters;
+    let matches = parse_regexes("filters.matches", filters.matches.as_ref())?;
+    layer.layer("matches", "filters.matches", &mut f.matches, matches);
+    let exclude = parse_regexes("filters.exclude_matches", filters.exclude_matches.as_ref())?;
+    layer.layer("exclude_matches", "filters.exclude_matches", &mut f.exclude_matches, exclude);
+    layer.layer("keywords", "filters.keywords", &mut f.keywords, filters.keywords.clone());
+    layer.layer("exclude_keywords", "filters.exclude_keywords", &mut f.exclude_keywords, filters.exclude_keywords.clone());
+    layer.layer("has_attachment", "filters.has_attachment", &mut f.has_attachment, filters.has_attachment);
+    layer.layer("no_attachment", "filters.no_attachment", &mut f.no_attachment, filters.no_attachment);
+    layer.layer("has_embed", "filters.has_embed", &mut f.has_embed, filters.has_embed);
+    layer.layer("no_embed", "filters.no_embed", &mut f.no_embed, filters.no_embed);
+    layer.layer("has_link", "filters.has_link", &mut f.has_link, filters.has_link);
+    layer.layer("no_link", "filters.no_link", &mut f.no_link, filters.no_link);
+    layer.layer("types", "filters.types", &mut f.types, filters.types.clone());
+
+    let pacing = &profile.pacing;
+    let delay = check_range("pacing.delay", pacing.delay, MIN_DELETE_DELAY..=MAX_DELETE_DELAY)?;
+    let delay_source = layer.layer("delay", "pacing.delay", &mut args.delay, delay);
+    let adaptive_source = layer.layer("adaptive", "pacing.adaptive", &mut args.adaptive, pacing.adaptive);
+    if args.adaptive && delay_source != Source::Default {
+        return Err(anyhow!(
+            "Adaptive pacing (from the {}) cannot be combined with a fixed delay (from the {})",
+            adaptive_source, delay_source
+        ));
+    }
+    let workers = check_range("pacing.workers", pacing.workers, 1..=16)?;
+    layer.layer("workers", "pacing.workers", &mut args.workers, workers);
+    layer.layer("max_requests_per_second", "pacing.max_requests_per_second", &mut args.max_requests_per_second, pacing.max_requests_per_second);
+    let strategy = pacing.strategy.as_deref().map(|s| parse_value_enum("pacing.strategy", s)).transpose()?;
+    layer.layer("strategy", "pacing.strategy", &mut args.strategy, strategy);
+    let page_size = check_range("pacing.page_size", pacing.page_size, 1..=MESSAGES_PER_REQUEST)?;
+    layer.layer("page_size", "pacing.page_size", &mut args.page_size, page_size);
+    let rounds = check_range("pacing.retry_rounds", pacing.retry_rounds, 0..=10)?;
+    layer.layer("retry_rounds", "pacing.retry_rounds", &mut args.retry.retry_rounds, rounds);
+
+    let backup = &profile.backup;
+    layer.layer("archive", "backup.archive", &mut args.archive, backup.archive.clone().map(Some));
+    let formats = backup.formats.as_ref()
+        .map(|formats| formats.iter().map(|f| parse_value_enum("backup.formats", f)).collect::<Result<Vec<_>>>())
+        .transpose()?;
+    layer.layer("archive_format", "backup.formats", &mut args.archive_format, formats);
+    layer.layer("download_attachments", "backup.download_attachments", &mut args.download_attachments, backup.download_attachments);
+    layer.layer("max_attachment_size", "backup.max_attachment_size", &mut args.max_attachment_size, backup.max_attachment_size);
+    let concurrency = check_range("backup.attachment_concurrency", backup.attachment_concurrency, 1..=32)?;
+    layer.layer("attachment_concurrency", "backup.attachment_concurrency", &mut args.attachment_concurrency, concurrency);
+    layer.layer("export_only", "backup.export_only", &mut args.export_only, backup.export_only);
+
+    let output = &profile.output;
+    layer.layer("output", "output.dry_run_file", &mut args.output, output.dry_run_file.clone().map(Some));
+    layer.layer("checkpoint_dir", "output.checkpoint_dir", &mut args.checkpoint_dir, output.checkpoint_dir.clone());
+    layer.layer("audit_db", "output.audit_db", &mut args.audit_db, output.audit_db.clone());
+    layer.layer("no_audit", "output.no_audit", &mut args.no_audit, output.no_audit);
+    layer.layer("retry_file", "output.retry_file", &mut args.retry.retry_file, output.retry_file.clone());
+    layer.layer("tui", "output.tui", &mut args.tui, output.tui);
+
+    check_delete(args)
+}
+
+/// Repeats the command line's own checks for combinations a profile can still produce.
+fn check_delete(args: &DeleteArgs) -> Result<()> {
+    if args.output.is_some() && !args.dry_run {
+        return Err(anyhow!("A dry-run output file needs --dry-run"));
+    }
+    if args.archive.is_some() && args.dry_run {
+        return Err(anyhow!("Archiving cannot be combined with --dry-run"));
+    }
+    if (args.download_attachments || args.export_only) && args.archive.is_none() {
+        return Err(anyhow!("Downloading attachments and --export-only need an archive directory"));
+    }
+    Ok(())
+}
+
+/// Prints the resolved settings of `config show`.
+pub fn show(config: &Config, settings: &[Setting]) {
+    match &config.path {
+        Some(path) => println!("Config file: {}", path.display()),
+        None => println!("Config file: none ({} not found)", DEFAULT_CONFIG_FILE),
+    }
+    println!("Profile: {}", config.name.as_deref().unwrap_or("none"));
+    println!();
+
+    let width = settings.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    for setting in settings {
+        println!("{:<width$}  {}  ({})", setting.name, setting.value, setting.source, width = width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    /// Parses `args` after the program name and applies `profile`, a profile in TOML.
+    fn resolve_with(profile: &str, args: &[&str]) -> Result<(Cli, Vec<Setting>)> {
+        let matches = Cli::command().try_get_matches_from(["discord_message_deleter"].iter().chain(args))?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        let config = Config { path: None, name: Some("test".to_string()), profile: toml::from_str(profile)? };
+        let settings = apply(&config, &mut cli, &matches)?;
+        Ok((cli, settings))
+    }
+
+    fn setting<'a>(settings: &'a [Setting], name: &str) -> (&'a str, Source) {
+        let setting = settings.iter().find(|s| s.name == name).unwrap();
+        (&setting.value, setting.source)
+    }
+
+    fn delete_args(cli: &Cli) -> &DeleteArgs {
+        match &cli.command {
+            Some(Command::Delete(args)) => args,
+            _ => panic!("not a delete command"),
+        }
+    }
+
+    #[test]
+    fn flags_win_over_the_profile_and_the_profile_over_defaults() {
+        let profile = "[pacing]\nworkers = 4\npage_size = 50";
+
+        let (cli, settings) = resolve_with(profile, &["delete", "--workers", "2"]).unwrap();
+
+        assert_eq!(delete_args(&cli).workers, 2);
+        assert_eq!(setting(&settings, "pacing.workers"), ("2", Source::Flag));
+        assert_eq!(delete_args(&cli).page_size, 50);
+        assert_eq!(setting(&settings, "pacing.page_size"), ("50", Source::Profile));
+        assert_eq!(setting(&settings, "pacing.retry_rounds"), ("3", Source::Default));
+    }
+
+    #[test]
+    fn the_environment_wins_over_the_profile_but_not_over_flags() {
+        let profile = r#"api_url = "http://profile.invalid""#;
+        env::set_var("DISCORD_API_URL", "http://env.invalid");
+
+        let from_env = resolve_with(profile, &[]);
+        let from_flag = resolve_with(profile, &["--api-url", "http://flag.invalid"]);
+        env::remove_var("DISCORD_API_URL");
+        let from_profile = resolve_with(profile, &[]);
+
+        let (cli, settings) = from_env.unwrap();
+        assert_eq!(cli.api_url, "http://env.invalid");
+        assert_eq!(setting(&settings, "api_url").1, Source::Env);
+        let (cli, settings) = from_flag.unwrap();
+        assert_eq!(cli.api_url, "http://flag.invalid");
+        assert_eq!(setting(&settings, "api_url").1, Source::Flag);
+        let (cli, settings) = from_profile.unwrap();
+        assert_eq!(cli.api_url, "http://profile.invalid");
+        assert_eq!(setting(&settings, "api_url").1, Source::Profile);
+    }
+
+    #[test]
+    fn command_line_targets_replace_the_profile_channels() {
+        let profile = r#"channels = ["111111111111111111"]"#;
+
+        let (cli, _) = resolve_with(profile, &["delete", "--guild", "222222222222222222"]).unwrap();
+
+        assert!(delete_args(&cli).channels.is_empty());
+    }
+
+    #[test]
+    fn config_show_masks_the_token() {
+        let token = "MTIzNDU2Nzg5MDEyMzQ1Njc4.secret-part.signature";
+
+        let (_, settings) = resolve_with("", &["--token", token, "config", "show"]).unwrap();
+
+        assert_eq!(setting(&settings, "token"), ("MTIz...ture", Source::Flag));
+        assert!(settings.iter().all(|s| !s.value.contains("secret-part")));
+    }
+
+    #[test]
+    fn short_tokens_are_masked_completely() {
+        assert_eq!(mask("short-token"), "***********");
+        assert_eq!(mask(""), "");
+    }
+
+    #[test]
+    fn an_adaptive_profile_conflicts_with_a_delay_flag() {
+        let error = resolve_with("[pacing]\nadaptive = true", &["delete", "--delay", "500"]).unwrap_err();
+        assert!(error.to_string().contains("Adaptive pacing (from the profile)"), "{}", error);
+
+        let (cli, _) = resolve_with("[pacing]\nadaptive = true", &["delete"]).unwrap();
+        assert!(delete_args(&cli).adaptive);
+    }
+
+    #[test]
+    fn profile_combinations_the_command_line_rejects_are_rejected() {
+        let error = resolve_with("[backup]\ndownload_attachments = true", &["delete"]).unwrap_err();
+        assert!(error.to_string().contains("need an archive directory"), "{}", error);
+
+        let error = resolve_with("[backup]\narchive = \"backup\"", &["delete", "--dry-run"]).unwrap_err();
+        assert!(error.to_string().contains("cannot be combined with --dry-run"), "{}", error);
+
+        assert!(resolve_with("[backup]\narchive = \"backup\"\nexport_only = true", &["delete"]).is_ok());
+    }
+
+    #[test]
+    fn out_of_range_profile_values_are_rejected() {
+        let error = resolve_with("[pacing]\nworkers = 99", &["delete"]).unwrap_err();
+        assert!(error.to_string().contains("pacing.workers must be between 1 and 16"), "{}", error);
+    }
+}
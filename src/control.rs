@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// How often a paused client checks whether it may continue.
+const PAUSE_POLL: Duration = Duration::from_millis(200);
+
+/// Pauses, resumes, skips or stops running clients from another task, e.g. a UI.
+///
+/// Clients check it between two messages, so the message being deleted is always finished.
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct RunControl {
+    state: Arc<ControlState>,
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    /// Bumped by every skip; clients built before the bump stop.
+    skips: AtomicU64,
+}
+
+impl RunControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::SeqCst)
+    }
+
+    /// Stops the channels that are running now. Channels started afterwards are not affected.
+    pub fn skip_current(&self) {
+        self.state.skips.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Stops every channel, now and later.
+    pub fn stop(&self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.stopped.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.state.skips.load(Ordering::SeqCst)
+    }
+
+    /// Whether a client that started at `generation` should stop.
+    pub(crate) fn is_cancelled(&self, generation: u64) -> bool {
+        self.is_stopped() || self.generation() != generation
+    }
+
+    /// Returns once the run is not paused, or is cancelled.
+    pub(crate) async fn wait_while_paused(&self, generation: u64) {
+        while self.is_paused() && !self.is_cancelled(generation) {
+            sleep(PAUSE_POLL).await;
+        }
+    }
+
+    /// Sleeps for `duration`, waking up early if the client is cancelled.
+    pub(crate) async fn sleep(&self, generation: u64, duration: Duration) {
+        let deadline = tokio::time::Instant::now() + duration;
+        while !self.is_cancelled(generation) {
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                break;
+            }
+            sleep(PAUSE_POLL.min(deadline - now)).await;
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Keeps the Discord token out of plain-text files: `login` stores it in the OS keyring (the
+//! Secret Service on Linux) or, where no keyring is reachable, in a file encrypted with a
+//! passphrase.
+
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use std::{env, fmt, fs, io::Write, path::{Path, PathBuf}};
+use tracing::{debug, warn};
+
+const KEYRING_SERVICE: &str = "discord_message_deleter";
+const KEYRING_USER: &str = "token";
+
+/// Passphrase of the encrypted token file, for runs without a terminal to prompt on.
+pub const PASSPHRASE_ENV: &str = "DISCORD_TOKEN_PASSPHRASE";
+
+const FILE_VERSION: u32 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+
+/// Where a stored token lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Store {
+    Keyring,
+    File(PathBuf),
+}
+
+impl fmt::Display for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Store::Keyring => write!(f, "the OS keyring"),
+            Store::File(path) => write!(f, "the encrypted file {}", path.display()),
+        }
+    }
+}
+
+/// Contents of the encrypted token file; binary fields are hex encoded.
+#[derive(Serialize, Deserialize)]
+struct EncryptedToken {
+    version: u32,
+    /// Salt of the Argon2id key derived from the passphrase.
+    salt: String,
+    /// XChaCha20-Poly1305 nonce.
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Default location of the encrypted token file, in the user's config directory.
+pub fn default_token_file() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("discord_message_deleter")
+        .join("token.enc")
+}
+
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+}
+
+/// Stores `token` in the keyring, or in the encrypted `file` if `use_file` is set or no keyring
+/// is available.
+pub fn save(token: &str, file: &Path, use_file: bool) -> Result<Store> {
+    if !use_file {
+        match keyring_entry().and_then(|entry| entry.set_password(token)) {
+            Ok(()) => return Ok(Store::Keyring),
+            Err(e) => warn!("No keyring available ({}), storing the token in an encrypted file instead", e),
+        }
+    }
+
+    let passphrase = new_passphrase()?;
+    write_encrypted(file, token, &passphrase)?;
+    Ok(Store::File(file.to_path_buf()))
+}
+
+/// Returns the token saved by `login`, looking in the keyring first and then in `file`.
+pub fn load(file: &Path) -> Result<Option<(String, Store)>> {
+    match keyring_entry().and_then(|entry| entry.get_password()) {
+        Ok(token) => return Ok(Some((token, Store::Keyring))),
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => debug!("Keyring not available: {}", e),
+    }
+    if !file.exists() {
+        return Ok(None);
+    }
+
+    let passphrase = passphrase(&format!("Passphrase for {}: ", file.display()))?;
+    let token = read_encrypted(file, &passphrase)?;
+    Ok(Some((token, Store::File(file.to_path_buf()))))
+}
+
+/// Deletes the token from the keyring and `file`, returning the places it was found in.
+pub fn remove(file: &Path) -> Result<Vec<Store>> {
+    let mut removed = Vec::new();
+    match keyring_entry().and_then(|entry| entry.delete_credential()) {
+        Ok(()) => removed.push(Store::Keyring),
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => debug!("Keyring not available: {}", e),
+    }
+    if file.exists() {
+        fs::remove_file(file).with_context(|| format!("Failed to delete {}", file.display()))?;
+        removed.push(Store::File(file.to_path_buf()));
+    }
+    Ok(removed)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive a key from the passphrase: {}", e))?;
+    Ok(key)
+}
+
+fn write_encrypted(path: &Path, token: &str, passphrase: &str) -> Result<()> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, token.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt the token"))?;
+
+    let contents = serde_json::to_string_pretty(&EncryptedToken {
+        version: FILE_VERSION,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_encrypted(path: &Path, passphrase: &str) -> Result<String> {
+    let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let stored: EncryptedToken = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid token file {}", path.display()))?;
+    if stored.version != FILE_VERSION {
+        bail!("Unsupported token file version {} in {}", stored.version, path.display());
+    }
+
+    let decode = |field: &str, value: &str| {
+        hex::decode(value).with_context(|| format!("Invalid {} in token file {}", field, path.display()))
+    };
+    let salt = decode("salt", &stored.salt)?;
+    let nonce = decode("nonce", &stored.nonce)?;
+    if nonce.len() != NONCE_LENGTH {
+        bail!("Invalid nonce in token file {}", path.display());
+    }
+    let ciphertext = decode("ciphertext", &stored.ciphertext)?;
+
+    let cipher = XChaCha20Poly1305::new(&derive_key(passphrase, &salt)?.into());
+    let token = cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("Wrong passphrase, or {} is damaged", path.display()))?;
+    String::from_utf8(token).with_context(|| format!("Invalid token in {}", path.display()))
+}
+
+fn passphrase(prompt: &str) -> Result<String> {
+    let passphrase = match env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password(prompt).context("Failed to read the passphrase")?,
+    };
+    if passphrase.is_empty() {
+        bail!("The passphrase must not be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Asks for the passphrase of a new token file twice, unless it is set in the environment.
+fn new_passphrase() -> Result<String> {
+    if env::var_os(PASSPHRASE_ENV).is_some() {
+        return passphrase("");
+    }
+    let first = passphrase("New passphrase for the token file: ")?;
+    let second = passphrase("Repeat the passphrase: ")?;
+    if first != second {
+        bail!("The passphrases do not match");
+    }
+    Ok(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "MTIzNDU2Nzg5MDEyMzQ1Njc4.token.signature";
+
+    fn stored(path: &Path) -> EncryptedToken {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn a_token_file_reads_back_with_its_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("token.enc");
+
+        write_encrypted(&path, TOKEN, "correct horse").unwrap();
+
+        assert_eq!(read_encrypted(&path, "correct horse").unwrap(), TOKEN);
+        assert!(!fs::read_to_string(&path).unwrap().contains("signature"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn every_write_uses_a_fresh_salt_and_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("first.enc"), dir.path().join("second.enc"));
+
+        write_encrypted(&first, TOKEN, "correct horse").unwrap();
+        write_encrypted(&second, TOKEN, "correct horse").unwrap();
+
+        let (first, second) = (stored(&first), stored(&second));
+        assert_ne!(first.salt, second.salt);
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.ciphertext, second.ciphertext);
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.enc");
+        write_encrypted(&path, TOKEN, "correct horse").unwrap();
+
+        let error = read_encrypted(&path, "battery staple").unwrap_err();
+
+        assert!(error.to_string().starts_with("Wrong passphrase"), "{}", error);
+    }
+
+    #[test]
+    fn a_tampered_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.enc");
+        write_encrypted(&path, TOKEN, "correct horse").unwrap();
+
+        let mut token = stored(&path);
+        let flipped = if token.ciphertext.starts_with('0') { "1" } else { "0" };
+        token.ciphertext.replace_range(..1, flipped);
+        fs::write(&path, serde_json::to_string(&token).unwrap()).unwrap();
+
+        let error = read_encrypted(&path, "correct horse").unwrap_err();
+        assert!(error.to_string().contains("is damaged"), "{}", error);
+    }
+
+    #[test]
+    fn a_truncated_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.enc");
+        write_encrypted(&path, TOKEN, "correct horse").unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+
+        let error = read_encrypted(&path, "correct horse").unwrap_err();
+        assert!(error.to_string().starts_with("Invalid token file"), "{}", error);
+    }
+
+    #[test]
+    fn a_file_of_another_version_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.enc");
+        write_encrypted(&path, TOKEN, "correct horse").unwrap();
+
+        let mut token = stored(&path);
+        token.version = FILE_VERSION + 1;
+        fs::write(&path, serde_json::to_string(&token).unwrap()).unwrap();
+
+        let error = read_encrypted(&path, "correct horse").unwrap_err();
+        assert!(error.to_string().starts_with("Unsupported token file version"), "{}", error);
+    }
+}
@@ -0,0 +1,209 @@
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::{io, path::PathBuf};
+
+/// Discord JSON error code for a message that does not exist.
+const UNKNOWN_MESSAGE_CODE: u32 = 10008;
+/// Discord JSON error code for actions that system messages do not allow.
+const SYSTEM_MESSAGE_CODE: u32 = 50021;
+
+/// Errors returned by the library.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The token was rejected by Discord or cannot be sent as an `Authorization` header.
+    #[error("Invalid Discord token")]
+    InvalidToken,
+
+    /// The token has no access to the channel, or may not delete the message.
+    #[error("Missing access to {url}: {body}")]
+    MissingAccess { url: String, body: String },
+
+    /// The message was already deleted or never existed.
+    #[error("Unknown message {url}")]
+    UnknownMessage { url: String },
+
+    /// Some system messages (calls, recipient changes, thread starters...) cannot be deleted.
+    #[error("Cannot delete a system message ({kind})")]
+    SystemMessage { kind: &'static str },
+
+    /// Discord kept answering 429; `retry_after` is in seconds.
+    #[error("Rate limited, retry after {retry_after} seconds")]
+    RateLimited { retry_after: f64 },
+
+    #[error("Discord server error {status}: {body}")]
+    ServerError { status: StatusCode, body: String },
+
+    /// Discord answered with another unexpected status.
+    #[error("Request to {url} failed: {status} - {body}")]
+    Api {
+        status: StatusCode,
+        url: String,
+        body: String,
+    },
+
+    /// The request could not be sent or its response could not be read.
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The user declined to continue.
+    #[error("Operation aborted by user")]
+    Aborted,
+
+    /// The channel was skipped or the run stopped through a [`RunControl`](crate::control::RunControl).
+    #[error("Stopped by user")]
+    Cancelled,
+
+    /// An ID, option or builder field was missing or malformed.
+    #[error("{0}")]
+    InvalidInput(String),
+
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid checkpoint file {}: {source}", path.display())]
+    InvalidCheckpoint {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid retry queue file {}: {source}", path.display())]
+    InvalidRetryQueue {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Audit database error: {0}")]
+    Audit(#[from] rusqlite::Error),
+
+    #[error("Failed to write CSV archive: {0}")]
+    Csv(#[from] csv::Error),
+
+    /// An attachment could not be downloaded or failed its size or checksum check.
+    #[error("Attachment download failed: {0}")]
+    Download(String),
+
+    /// A background listing or deletion task panicked or was cancelled.
+    #[error("Background task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// How a caller should react to an [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Temporary; the same request may succeed if it is sent again after a pause.
+    Retryable,
+    /// Only this message (or channel) is affected; move on to the next one.
+    Skippable,
+    /// Nothing else will work either; stop the run.
+    Fatal,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    code: Option<u32>,
+    #[serde(default)]
+    retry_after: Option<f64>,
+}
+
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Error::RateLimited { .. } | Error::ServerError { .. } => Severity::Retryable,
+            Error::Network(e) if e.is_builder() => Severity::Fatal,
+            Error::Network(_) => Severity::Retryable,
+            Error::MissingAccess { .. }
+            | Error::UnknownMessage { .. }
+            | Error::SystemMessage { .. }
+            | Error::Cancelled
+            | Error::Api { .. }
+            | Error::Download(_) => Severity::Skippable,
+            Error::InvalidToken
+            | Error::Json(_)
+            | Error::Aborted
+            | Error::InvalidInput(_)
+            | Error::Io { .. }
+            | Error::InvalidCheckpoint { .. }
+            | Error::InvalidRetryQueue { .. }
+            | Error::Audit(_)
+            | Error::Csv(_)
+            | Error::Task(_) => Severity::Fatal,
+        }
+    }
+
+    /// HTTP status Discord answered with, for errors that came from a response.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::InvalidToken => Some(StatusCode::UNAUTHORIZED),
+            Error::MissingAccess { .. } => Some(StatusCode::FORBIDDEN),
+            Error::UnknownMessage { .. } => Some(StatusCode::NOT_FOUND),
+            Error::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            Error::ServerError { status, .. } | Error::Api { status, .. } => Some(*status),
+            Error::Network(e) => e.status(),
+            _ => None,
+        }
+    }
+
+    /// Classifies an unsuccessful API response by its status and Discord error code.
+    pub(crate) async fn from_response(response: Response, url: &str) -> Self {
+        let status = response.status();
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(e) => return Error::Network(e),
+        };
+        let parsed: Option<ErrorBody> = serde_json::from_str(&body).ok();
+        let code = parsed.as_ref().and_then(|b| b.code);
+
+        match status {
+            StatusCode::UNAUTHORIZED => Error::InvalidToken,
+            _ if code == Some(SYSTEM_MESSAGE_CODE) => Error::SystemMessage { kind: "system message" },
+            StatusCode::FORBIDDEN => Error::MissingAccess { url: url.to_string(), body },
+            StatusCode::NOT_FOUND if code == Some(UNKNOWN_MESSAGE_CODE) || (code.is_none() && url.contains("/messages/")) => {
+                Error::UnknownMessage { url: url.to_string() }
+            }
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+                retry_after: parsed.and_then(|b| b.retry_after).unwrap_or(1.0),
+            },
+            status if status.is_server_error() => Error::ServerError { status, body },
+            status => Error::Api { status, url: url.to_string(), body },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io {
+            context: "I/O error".to_string(),
+            source,
+        }
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for Error {
+    fn from(_: reqwest::header::InvalidHeaderValue) -> Self {
+        Error::InvalidToken
+    }
+}
+
+/// Adds a description of the failed operation to I/O errors.
+pub(crate) trait IoContext<T> {
+    fn io_context<F: FnOnce() -> String>(self, context: F) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::result::Result<T, io::Error> {
+    fn io_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| Error::Io { context: context(), source })
+    }
+}
@@ -0,0 +1,72 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::Message;
+
+static LINK_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:https?://|www\.)\S+").expect("valid link pattern"));
+
+/// A predicate deciding whether a message should be deleted.
+///
+/// Filters compose with [`MessageFilter::All`], [`MessageFilter::Any`] and [`MessageFilter::Not`],
+/// so every command-line option maps to one node of the tree.
+#[derive(Debug, Clone)]
+pub enum MessageFilter {
+    /// Message content matches the regular expression.
+    Regex(Regex),
+    /// Message content contains at least one of the keywords, ignoring case.
+    Keywords(Vec<String>),
+    /// Message has at least one attachment.
+    HasAttachment,
+    /// Message has at least one embed.
+    HasEmbed,
+    /// Message content contains a link.
+    HasLink,
+    /// Message is one of the given Discord message types.
+    Type(Vec<u8>),
+    Not(Box<MessageFilter>),
+    All(Vec<MessageFilter>),
+    Any(Vec<MessageFilter>),
+}
+
+impl Default for MessageFilter {
+    fn default() -> Self {
+        Self::everything()
+    }
+}
+
+impl MessageFilter {
+    /// A filter that accepts every message.
+    pub fn everything() -> Self {
+        MessageFilter::All(Vec::new())
+    }
+
+    pub fn keywords<I, S>(keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        MessageFilter::Keywords(keywords.into_iter().map(|k| k.as_ref().to_lowercase()).collect())
+    }
+
+    pub fn negate(self) -> Self {
+        MessageFilter::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        match self {
+            MessageFilter::Regex(regex) => regex.is_match(&message.content),
+            MessageFilter::Keywords(keywords) => {
+                let content = message.content.to_lowercase();
+                keywords.iter().any(|k| content.contains(k.as_str()))
+            }
+            MessageFilter::HasAttachment => !message.attachments.is_empty(),
+            MessageFilter::HasEmbed => !message.embeds.is_empty(),
+            MessageFilter::HasLink => LINK_PATTERN.is_match(&message.content),
+            MessageFilter::Type(types) => types.contains(&message.kind),
+            MessageFilter::Not(inner) => !inner.matches(message),
+            MessageFilter::All(filters) => filters.iter().all(|f| f.matches(message)),
+            MessageFilter::Any(filters) => filters.iter().any(|f| f.matches(message)),
+        }
+    }
+}
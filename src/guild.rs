@@ -0,0 +1,116 @@
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::HashSet;
+use tracing::warn;
+
+use crate::{client::read_json, error::{Error, Result}, ratelimit::{self, RateLimiter}, transport::Transport};
+
+/// Channel types that hold messages directly: text, voice, announcement, threads and stage.
+const MESSAGE_CHANNEL_TYPES: [u8; 7] = [0, 2, 5, 10, 11, 12, 13];
+
+/// Channel types whose archived threads are listed: text, announcement, forum and media.
+const THREAD_PARENT_TYPES: [u8; 4] = [0, 5, 15, 16];
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GuildChannel {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub channel_type: u8,
+    #[serde(default)]
+    thread_metadata: Option<ThreadMetadata>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ThreadMetadata {
+    archive_timestamp: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThreadList {
+    threads: Vec<GuildChannel>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+impl GuildChannel {
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("#{} ({})", name, self.id),
+            None => self.id.clone(),
+        }
+    }
+}
+
+/// Lists every channel, thread and forum post in a guild that can contain messages.
+///
+/// Archived threads are fetched per parent channel; parents we cannot read are skipped, as are
+/// active threads when the guild's thread list cannot be read.
+pub async fn list_guild_channels(transport: &Transport, token: &str, api_base: &str, guild_id: &str) -> Result<Vec<GuildChannel>> {
+    let channels: Vec<GuildChannel> =
+        get_json(transport, token, &format!("{}/guilds/{}/channels", api_base, guild_id)).await?;
+    let active = match get_json::<ThreadList>(transport, token, &format!("{}/guilds/{}/threads/active", api_base, guild_id)).await {
+        Ok(active) => active.threads,
+        Err(e) => {
+            warn!(guild = %guild_id, error = %e, "Skipping active threads");
+            Vec::new()
+        }
+    };
+
+    let mut result = Vec::new();
+    let mut seen = HashSet::new();
+    let mut add = |channel: GuildChannel| {
+        if MESSAGE_CHANNEL_TYPES.contains(&channel.channel_type) && seen.insert(channel.id.clone()) {
+            result.push(channel);
+        }
+    };
+
+    for thread in active {
+        add(thread);
+    }
+
+    for channel in channels {
+        if THREAD_PARENT_TYPES.contains(&channel.channel_type) {
+            match list_archived_threads(transport, token, api_base, &channel.id).await {
+                Ok(threads) => threads.into_iter().for_each(&mut add),
+                Err(e) => warn!(channel = %channel.display_name(), error = %e, "Skipping archived threads"),
+            }
+        }
+        add(channel);
+    }
+
+    Ok(result)
+}
+
+async fn list_archived_threads(transport: &Transport, token: &str, api_base: &str, channel_id: &str) -> Result<Vec<GuildChannel>> {
+    let mut threads = Vec::new();
+    let mut before: Option<String> = None;
+
+    loop {
+        let mut url = format!("{}/channels/{}/threads/archived/public?limit=100", api_base, channel_id);
+        if let Some(timestamp) = &before {
+            url.push_str(&format!("&before={}", timestamp));
+        }
+
+        let page: ThreadList = get_json(transport, token, &url).await?;
+        before = page.threads.last()
+            .and_then(|t| t.thread_metadata.as_ref())
+            .map(|m| m.archive_timestamp.clone());
+        threads.extend(page.threads);
+
+        if !page.has_more || before.is_none() {
+            return Ok(threads);
+        }
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(transport: &Transport, token: &str, url: &str) -> Result<T> {
+    let response = ratelimit::send(transport, token, &RateLimiter::shared(), None, Method::GET, url).await?;
+
+    if response.status().is_success() {
+        read_json(response).await
+    } else {
+        Err(Error::from_response(response, url).await)
+    }
+}
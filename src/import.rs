@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    error::{Error, IoContext, Result},
+    snowflake::{timestamp_from_snowflake, validate_snowflake},
+    Attachment, Author, Message,
+};
+
+/// One message listed in a data package or ID list.
+#[derive(Debug, Clone)]
+pub struct ImportedMessage {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    /// Empty when the source only has IDs.
+    pub content: String,
+    pub attachment_urls: Vec<String>,
+}
+
+/// The imported messages of one channel.
+#[derive(Debug, Clone)]
+pub struct ImportedChannel {
+    pub channel_id: String,
+    /// Name from the package's `index.json`, e.g. "Direct Message with someone".
+    pub name: Option<String>,
+    pub messages: Vec<ImportedMessage>,
+}
+
+/// A row of `messages.csv` or an entry of `messages.json`.
+#[derive(Debug, Deserialize)]
+struct PackageMessage {
+    #[serde(rename = "ID")]
+    id: serde_json::Value,
+    #[serde(rename = "Contents", default)]
+    contents: String,
+    #[serde(rename = "Attachments", default)]
+    attachments: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageChannel {
+    id: String,
+}
+
+impl ImportedMessage {
+    fn new(id: String, content: String, attachment_urls: Vec<String>) -> Option<Self> {
+        if !validate_snowflake(&id) {
+            return None;
+        }
+        let timestamp = timestamp_from_snowflake(&id)?;
+        Some(Self { id, timestamp, content, attachment_urls })
+    }
+}
+
+impl ImportedChannel {
+    /// Turns the imported entries into messages by `author_id`, so they can go through the
+    /// usual filters and deletion pipeline.
+    pub fn to_messages(&self, author_id: &str) -> Vec<Message> {
+        self.messages.iter().map(|imported| Message {
+            id: imported.id.clone(),
+            author: Author {
+                id: author_id.to_string(),
+                username: String::new(),
+                global_name: None,
+                extra: Default::default(),
+            },
+            content: imported.content.clone(),
+            timestamp: imported.timestamp,
+            attachments: imported.attachment_urls.iter().map(|url| attachment_from_url(url)).collect(),
+            embeds: Vec::new(),
+            kind: 0,
+            message_reference: None,
+            extra: Default::default(),
+        }).collect()
+    }
+}
+
+/// Rebuilds what the package knows of an attachment from its CDN URL
+/// (`.../attachments/<channel>/<attachment>/<filename>`).
+fn attachment_from_url(url: &str) -> Attachment {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let mut segments = path.rsplit('/');
+    let filename = segments.next().unwrap_or_default().to_string();
+    let id = segments.next().unwrap_or_default().to_string();
+    Attachment { id, filename, url: url.to_string(), size: None, extra: Default::default() }
+}
+
+/// Finds the messages directory of an extracted data package; `dir` may be the package root or
+/// the messages directory itself.
+fn messages_dir(dir: &Path) -> Result<PathBuf> {
+    for candidate in [dir.join("messages"), dir.join("Messages"), dir.to_path_buf()] {
+        if candidate.join("index.json").is_file() {
+            return Ok(candidate);
+        }
+    }
+    Err(Error::InvalidInput(format!(
+        "{} is not an extracted Discord data package (no messages/index.json found)",
+        dir.display()
+    )))
+}
+
+fn invalid_file(path: &Path, reason: impl std::fmt::Display) -> Error {
+    Error::InvalidInput(format!("Invalid data package file {}: {}", path.display(), reason))
+}
+
+/// Reads every channel's messages from an extracted Discord data package.
+///
+/// Supports both layouts Discord has used: `messages/c<channel>/messages.json` and the older
+/// `messages/<channel>/messages.csv`, each with a `channel.json` next to it.
+pub fn read_data_package(dir: &Path) -> Result<Vec<ImportedChannel>> {
+    let messages_dir = messages_dir(dir)?;
+    let index_path = messages_dir.join("index.json");
+    let index: HashMap<String, Option<String>> = serde_json::from_str(
+        &fs::read_to_string(&index_path).io_context(|| format!("Failed to read {}", index_path.display()))?,
+    )
+    .map_err(|e| invalid_file(&index_path, e))?;
+
+    let mut channels = Vec::new();
+    let entries = fs::read_dir(&messages_dir)
+        .io_context(|| format!("Failed to read {}", messages_dir.display()))?;
+    for entry in entries {
+        let channel_dir = entry.io_context(|| format!("Failed to read {}", messages_dir.display()))?.path();
+        if !channel_dir.is_dir() {
+            continue;
+        }
+
+        let Some(channel_id) = package_channel_id(&channel_dir)? else {
+            debug!(dir = %channel_dir.display(), "Skipping directory without a channel ID");
+            continue;
+        };
+        let messages = read_channel_messages(&channel_dir)?;
+        if messages.is_empty() {
+            continue;
+        }
+
+        channels.push(ImportedChannel {
+            name: index.get(&channel_id).cloned().flatten(),
+            channel_id,
+            messages,
+        });
+    }
+
+    channels.sort_by(|a, b| a.channel_id.cmp(&b.channel_id));
+    info!(
+        channels = channels.len(),
+        messages = channels.iter().map(|c| c.messages.len()).sum::<usize>(),
+        "Read data package"
+    );
+    Ok(channels)
+}
+
+fn package_channel_id(channel_dir: &Path) -> Result<Option<String>> {
+    let channel_path = channel_dir.join("channel.json");
+    let id = if channel_path.is_file() {
+        let data = fs::read_to_string(&channel_path)
+            .io_context(|| format!("Failed to read {}", channel_path.display()))?;
+        let channel: PackageChannel = serde_json::from_str(&data).map_err(|e| invalid_file(&channel_path, e))?;
+        channel.id
+    } else {
+        let name = channel_dir.file_name().unwrap_or_default().to_string_lossy();
+        name.strip_prefix('c').unwrap_or(&name).to_string()
+    };
+    Ok(validate_snowflake(&id).then_some(id))
+}
+
+fn read_channel_messages(channel_dir: &Path) -> Result<Vec<ImportedMessage>> {
+    let json_path = channel_dir.join("messages.json");
+    let csv_path = channel_dir.join("messages.csv");
+
+    let rows: Vec<PackageMessage> = if json_path.is_file() {
+        let data = fs::read_to_string(&json_path)
+            .io_context(|| format!("Failed to read {}", json_path.display()))?;
+        serde_json::from_str(&data).map_err(|e| invalid_file(&json_path, e))?
+    } else if csv_path.is_file() {
+        csv::Reader::from_path(&csv_path)
+            .and_then(|mut reader| reader.deserialize().collect())
+            .map_err(|e| invalid_file(&csv_path, e))?
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let mut messages = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id = match row.id {
+            serde_json::Value::String(id) => id,
+            other => other.to_string(),
+        };
+        let urls = row.attachments.split_whitespace().map(str::to_string).collect();
+        match ImportedMessage::new(id.clone(), row.contents, urls) {
+            Some(message) => messages.push(message),
+            None => warn!(dir = %channel_dir.display(), %id, "Skipping message with an invalid ID"),
+        }
+    }
+    Ok(messages)
+}
+
+/// Reads a plain list of channel and message IDs, one message per line.
+///
+/// The two IDs may be separated by a comma, tab or spaces, and message links
+/// (`https://discord.com/channels/<guild>/<channel>/<message>`) work too. Blank lines, lines
+/// starting with `#` and lines without any ID (such as a CSV header) are ignored.
+pub fn read_id_list(path: &Path) -> Result<Vec<ImportedChannel>> {
+    let data = fs::read_to_string(path).io_context(|| format!("Failed to read ID list {}", path.display()))?;
+
+    let mut channels: BTreeMap<String, Vec<ImportedMessage>> = BTreeMap::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let ids: Vec<&str> = line.split(|c: char| !c.is_ascii_digit()).filter(|s| !s.is_empty()).collect();
+        if ids.is_empty() {
+            continue;
+        }
+        let (channel_id, message_id) = match ids.as_slice() {
+            [.., channel_id, message_id] if validate_snowflake(channel_id) && validate_snowflake(message_id) => {
+                (channel_id.to_string(), message_id.to_string())
+            }
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "{} line {}: expected a channel ID and a message ID, got '{}'",
+                    path.display(),
+                    number + 1,
+                    line
+                )))
+            }
+        };
+
+        if let Some(message) = ImportedMessage::new(message_id, String::new(), Vec::new()) {
+            channels.entry(channel_id).or_default().push(message);
+        }
+    }
+
+    Ok(channels.into_iter()
+        .map(|(channel_id, messages)| ImportedChannel { channel_id, name: None, messages })
+        .collect())
+}
@@ -0,0 +1,62 @@
+//! Bulk deletion of your own Discord messages.
+//!
+//! [`DiscordClient`] lists one user's messages in a channel, optionally archives them, and
+//! deletes them while respecting Discord's rate limits. Messages can be consumed as a
+//! [`Stream`](futures::Stream) with [`DiscordClient::messages`], or handed to the built-in
+//! executor with [`DiscordClient::delete_all_messages`], which reports [`Progress`] events.
+//!
+//! ```no_run
+//! use discord_message_deleter::{filter::MessageFilter, DiscordClient, Progress, RunOptions};
+//!
+//! # async fn run() -> discord_message_deleter::Result<()> {
+//! let client = DiscordClient::builder("token")
+//!     .channel("123456789012345678")
+//!     .author("876543210987654321")
+//!     .options(RunOptions {
+//!         filter: MessageFilter::keywords(vec!["secret".to_string()]),
+//!         ..RunOptions::default()
+//!     })
+//!     .on_progress(|event| {
+//!         if let Progress::Deleted { message_id } = event {
+//!             eprintln!("deleted {}", message_id);
+//!         }
+//!     })
+//!     .build()?;
+//!
+//! let stats = client.delete_all_messages().await?;
+//! println!("{} deleted, {} failed", stats.deleted, stats.failed);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod archive;
+pub mod attachments;
+pub mod audit;
+pub mod checkpoint;
+mod client;
+pub mod control;
+pub mod error;
+pub mod filter;
+pub mod guild;
+pub mod import;
+mod model;
+pub mod pacing;
+mod progress;
+pub mod ratelimit;
+pub mod retry;
+pub mod search;
+pub mod snowflake;
+pub mod transport;
+
+pub use client::{ClientBuilder, DiscordClient, RunOptions};
+pub use control::RunControl;
+pub use error::{Error, Result};
+pub use model::{Attachment, Author, ChannelInfo, CurrentUser, Message, MessageReference, User};
+pub use progress::{Progress, ProgressCallback, Prompt, StatsSnapshot};
+
+pub const DISCORD_API: &str = "https://discord.com/api/v10";
+pub const MESSAGES_PER_REQUEST: u32 = 100;
+pub const MIN_DELETE_DELAY: u64 = 0;
+pub const MAX_DELETE_DELAY: u64 = 5000;
+/// Characters of message content shown in dry-run reports.
+pub const PREVIEW_LENGTH: usize = 80;
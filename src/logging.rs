@@ -0,0 +1,57 @@
+use anyhow::{Context, Result};
+use std::{fs::OpenOptions, path::Path, sync::Mutex};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::tui::LogBuffer;
+
+/// Sets up console logging at the level picked by `--quiet`/`--verbose` (`RUST_LOG` overrides
+/// it), plus an optional JSON log file that always records debug events for later audits.
+///
+/// With a `dashboard` buffer, console events go to the dashboard's log pane instead of stderr.
+pub fn init(quiet: bool, verbose: u8, log_file: Option<&Path>, dashboard: Option<LogBuffer>) -> Result<()> {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::WARN,
+        (false, 0) => LevelFilter::INFO,
+        (false, 1) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    let console_filter = || EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+
+    let (console, dashboard) = match dashboard {
+        Some(buffer) => (
+            None,
+            Some(fmt::layer().with_writer(buffer).with_ansi(false).with_target(false).with_filter(console_filter())),
+        ),
+        None => (
+            Some(fmt::layer().with_writer(std::io::stderr).with_target(false).with_filter(console_filter())),
+            None,
+        ),
+    };
+
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            Some(
+                fmt::layer()
+                    .json()
+                    .with_writer(Mutex::new(file))
+                    .with_filter(LevelFilter::DEBUG.max(level)),
+            )
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(dashboard)
+        .with(file)
+        .try_init()
+        .context("Failed to set up logging")
+}
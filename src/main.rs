@@ -1,586 +1,985 @@
+mod cli;
+mod config;
+mod credentials;
+mod logging;
+mod tui;
+
 use anyhow::{Context, Result, anyhow};
+use clap::{CommandFactory, FromArgMatches};
+use cli::{Cli, Command, ConfigCommand, DeleteArgs, HistoryArgs, LoginArgs, RetryArgs, EXIT_FATAL, EXIT_PARTIAL_FAILURE};
+use discord_message_deleter::{
+    attachments::AttachmentOptions,
+    audit::{AuditLog, AuditRun, RecordQuery, DEFAULT_AUDIT_DB},
+    checkpoint::{Checkpoint, DEFAULT_CHECKPOINT_DIR},
+    error::Severity,
+    filter::MessageFilter,
+    guild, import,
+    ratelimit::RateLimiter,
+    retry::{FailedDeletion, RetryQueue, DEFAULT_RETRY_FILE},
+    search::ListingStrategy,
+    snowflake::validate_snowflake,
+    transport::Transport,
+    ChannelInfo, CurrentUser, DiscordClient, Error, Message, Progress, ProgressCallback, RunControl, RunOptions, StatsSnapshot, MAX_DELETE_DELAY, MESSAGES_PER_REQUEST, MIN_DELETE_DELAY,
+    PREVIEW_LENGTH,
+};
 use dotenv::dotenv;
-use reqwest::{header, Client, StatusCode};
-use serde::Deserialize;
-use std::{env, time::{Duration, Instant}, io::{self, Write}, sync::{Arc, Mutex}};
-use tokio::{time::sleep, sync::mpsc};
-
-const DISCORD_API: &str = "https://discord.com/api/v10";
-const MESSAGES_PER_REQUEST: u32 = 100;
-const MIN_DELETE_DELAY: u64 = 50;
-const MAX_DELETE_DELAY: u64 = 5000;
-
-#[derive(Debug, Deserialize, Clone)]
-struct Message {
-    id: String,
-    author: Author,
+use futures::stream::{self, StreamExt};
+use std::{env, fs::File, path::{Path, PathBuf}, process::ExitCode, io::{self, BufWriter, Write}, sync::{mpsc::Sender, Arc, Mutex}};
+use tracing::{error, info, warn};
+use config::{Config, Setting};
+use tui::{Dashboard, LogBuffer, Update};
+
+/// Result of one channel in a multi-channel run.
+struct ChannelSummary {
+    channel: String,
+    deleted: usize,
+    failed: usize,
+    skipped: usize,
+    system: usize,
+    matched: usize,
+    error: Option<String>,
+    /// The error makes every other channel fail too, so the run stops.
+    fatal: bool,
+    /// Deletions still failing after the retry rounds.
+    retry_later: Vec<FailedDeletion>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-struct Author {
-    id: String,
+fn read_input(prompt: &str) -> Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
 }
 
-#[derive(Debug, Deserialize)]
-struct RateLimitResponse {
-    retry_after: f64,
-}
+fn read_number_input(prompt: &str, min: u64, max: u64, default: u64) -> Result<u64> {
+    let input = read_input(prompt)?;
+    if input.is_empty() {
+        return Ok(default);
+    }
 
-#[derive(Debug, Deserialize)]
-struct ChannelInfo {
-    #[serde(default)]
-    name: Option<String>,
-    #[serde(rename = "type")]
-    channel_type: u8,
-    recipients: Option<Vec<User>>,
+    match input.parse::<u64>() {
+        Ok(num) if num >= min && num <= max => Ok(num),
+        Ok(_) => Err(anyhow!("Value must be between {} and {}", min, max)),
+        Err(_) => Err(anyhow!("Invalid number format")),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct User {
-    username: String,
-    discriminator: String,
+fn print_channel_info(info: &ChannelInfo) {
+    println!("\nChannel Information:");
+    println!("------------------");
+    match info.channel_type {
+        1 => {
+            println!("Type: Direct Message (DM)");
+            if let Some(recipients) = &info.recipients {
+                for user in recipients {
+                    println!("With User: {}#{}", user.username, user.discriminator);
+                }
+            }
+        },
+        3 => println!("Type: Group DM"),
+        0 | 2 | 4 | 5 | 6 => {
+            println!("Type: Server Channel");
+            if let Some(name) = &info.name {
+                println!("Channel Name: #{}", name);
+            }
+        },
+        _ => println!("Type: Unknown Channel Type"),
+    }
+    println!("------------------");
 }
 
-struct Stats {
-    total_deleted: usize,
-    total_failed: usize,
-    start_time: Instant,
-    messages_in_process: usize,
+/// How a command reports to the terminal.
+struct Console {
+    /// Only the final summary is printed; logs are cut down to warnings and errors.
+    quiet: bool,
+    /// Log pane of the live dashboard, when `--tui` is on.
+    dashboard_log: Option<LogBuffer>,
 }
 
-struct DiscordClient {
-    client: Client,
-    channel_id: String,
+/// Settings shared by every channel of a `delete` run.
+struct DeleteRun {
+    token: String,
+    api_url: String,
     author_id: String,
-    stats: Arc<Mutex<Stats>>,
-    delete_delay: u64,
+    options: RunOptions,
+    progress: Option<ProgressCallback>,
+    audit: Option<AuditRun>,
+    interactive: bool,
+    /// Only the final summary is printed.
+    quiet: bool,
+    control: Option<RunControl>,
+    /// Updates for the live dashboard; channel details and results are not printed with it.
+    dashboard: Option<Sender<Update>>,
 }
 
-impl DiscordClient {
-    async fn validate_token(token: &str) -> Result<bool> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(token)?,
-        );
-
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()?;
+/// Asks a yes/no question that defaults to yes.
+fn confirm(prompt: &str) -> bool {
+    read_input(prompt).is_ok_and(|answer| answer.to_lowercase() != "n")
+}
 
-        let response = client.get("https://discord.com/api/v10/users/@me")
-            .send()
-            .await?;
+/// A channel to clean up.
+struct Target {
+    channel_id: String,
+    label: String,
+    /// Messages to delete from a data package or ID list; `None` lists the channel instead.
+    messages: Option<Vec<Message>>,
+}
 
-        Ok(response.status().is_success())
+impl Target {
+    fn channel(channel_id: String, label: String) -> Self {
+        Self { channel_id, label, messages: None }
     }
+}
 
-    async fn validate_channel(token: &str, channel_id: &str) -> Result<bool> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(token)?,
-        );
+/// Runs the list/delete pipeline for one channel. Errors inside the pipeline are recorded in the
+/// summary so other channels keep going; only a failure to create the client is returned.
+async fn process_channel(run: &DeleteRun, index: usize, target: Target) -> Result<ChannelSummary> {
+    let Target { channel_id, label, messages } = target;
+    match &run.dashboard {
+        Some(dashboard) => {
+            let _ = dashboard.send(Update::ChannelStarted { index, label: label.clone() });
+        }
+        None if run.quiet => {}
+        None => println!("\n=== Channel {} ({}) ===", index + 1, label),
+    }
+    let guild_id = match DiscordClient::get_channel_info(Transport::shared()?, &run.api_url, &run.token, &channel_id).await {
+        Ok(info) => {
+            if run.dashboard.is_none() && !run.quiet {
+                print_channel_info(&info);
+            }
+            info.guild_id
+        }
+        Err(e) => {
+            warn!(%channel_id, error = %e, "Could not get channel details");
+            None
+        }
+    };
+
+    let mut builder = DiscordClient::builder(run.token.as_str())
+        .api_base(run.api_url.as_str())
+        .channel(channel_id)
+        .author(run.author_id.as_str())
+        .options(run.options.clone());
+    if let Some(guild_id) = guild_id {
+        builder = builder.guild(guild_id);
+    }
+    match (&run.progress, &run.dashboard) {
+        (progress, Some(dashboard)) => {
+            let progress = progress.clone();
+            let dashboard = dashboard.clone();
+            builder = builder.on_progress(move |event| {
+                if let Some(progress) = &progress {
+                    progress(event);
+                }
+                let _ = dashboard.send(Update::Progress(event.clone()));
+            });
+        }
+        (Some(progress), None) => builder = builder.progress_callback(Arc::clone(progress)),
+        (None, None) => {}
+    }
+    if let Some(control) = &run.control {
+        builder = builder.control(control.clone());
+    }
+    if let Some(audit) = &run.audit {
+        builder = builder.audit(audit.clone());
+    }
+    if let Some(messages) = messages {
+        builder = builder.known_messages(messages);
+    }
+    if run.interactive {
+        builder = builder.prompt(confirm);
+    }
+    let discord = builder.build()?;
 
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()?;
+    let result = discord.delete_all_messages().await;
+    let stats = discord.stats();
+    if run.dashboard.is_none() && !run.quiet {
+        print_channel_result(&stats, &run.options, discord.channel_id());
+    }
+    let mut summary = ChannelSummary {
+        channel: label,
+        deleted: stats.deleted,
+        failed: stats.failed,
+        skipped: stats.skipped,
+        system: stats.system,
+        matched: stats.matched,
+        error: None,
+        fatal: false,
+        retry_later: discord.failed_deletions(),
+    };
+    if let Err(e) = result {
+        error!(channel = %summary.channel, error = %e, "Error processing channel");
+        summary.error = Some(e.to_string());
+        summary.fatal = e.severity() == Severity::Fatal;
+    }
+    if let Some(dashboard) = &run.dashboard {
+        let _ = dashboard.send(Update::ChannelFinished {
+            index,
+            label: summary.channel.clone(),
+            error: summary.error.clone(),
+        });
+    }
+    Ok(summary)
+}
 
-        let response = client.get(&format!("https://discord.com/api/v10/channels/{}", channel_id))
-            .send()
-            .await?;
+/// Prints the counters of one finished (or failed) channel.
+fn print_channel_result(stats: &StatsSnapshot, options: &RunOptions, channel_id: &str) {
+    let minutes = stats.elapsed.as_secs() / 60;
+    let seconds = stats.elapsed.as_secs() % 60;
+
+    println!("\nOperation Complete!");
+    if options.dry_run {
+        println!("Messages that would be deleted: {}", stats.matched);
+    } else if options.export_only {
+        println!("Messages archived: {}", stats.archived);
+        println!("Failed to archive: {}", stats.failed);
+    } else {
+        println!("Successfully deleted: {}", stats.deleted);
+        println!("Failed to delete: {}", stats.failed);
+    }
+    if stats.skipped > 0 {
+        println!("Skipped (already gone or not deletable): {}", stats.skipped);
+    }
+    if stats.system > 0 {
+        println!("System messages left alone (calls, recipient changes, thread starters...): {}", stats.system);
+    }
+    if let Some(dir) = &options.archive_dir {
+        println!("Archive written to: {}", dir.join(channel_id).display());
+    }
+    println!("Total time elapsed: {}m {}s", minutes, seconds);
+}
 
-        Ok(response.status().is_success())
+fn print_run_summary(summaries: &[ChannelSummary], dry_run: bool) {
+    println!("\nSummary for {} channels:", summaries.len());
+    for summary in summaries {
+        match (&summary.error, dry_run) {
+            (Some(error), _) => println!("{}: error - {}", summary.channel, error),
+            (None, true) => println!("{}: {} would be deleted", summary.channel, summary.matched),
+            (None, false) => println!(
+                "{}: {} deleted, {} failed, {} skipped, {} system",
+                summary.channel, summary.deleted, summary.failed, summary.skipped, summary.system
+            ),
+        }
     }
 
-    fn new(token: String, channel_id: String, author_id: String, delete_delay: u64) -> Result<Self> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(&token)?,
-        );
+    let errors = summaries.iter().filter(|s| s.error.is_some()).count();
+    if dry_run {
+        println!("Total messages that would be deleted: {}", summaries.iter().map(|s| s.matched).sum::<usize>());
+    } else {
+        println!("Total deleted: {}", summaries.iter().map(|s| s.deleted).sum::<usize>());
+        println!("Total failed: {}", summaries.iter().map(|s| s.failed).sum::<usize>());
+        println!("Total skipped: {}", summaries.iter().map(|s| s.skipped).sum::<usize>());
+        println!("Total system messages left alone: {}", summaries.iter().map(|s| s.system).sum::<usize>());
+    }
+    if errors > 0 {
+        println!("Channels with errors: {}", errors);
+    }
+}
 
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .context("Failed to create HTTP client")?;
-
-        Ok(Self {
-            client,
-            channel_id,
-            author_id,
-            stats: Arc::new(Mutex::new(Stats {
-                total_deleted: 0,
-                total_failed: 0,
-                start_time: Instant::now(),
-                messages_in_process: 0,
-            })),
-            delete_delay,
-        })
+/// Prints the settings of a `delete` run before it starts.
+fn print_configuration(targets: &[Target], options: &RunOptions, author: &str, imported_from: Option<&PathBuf>) {
+    println!("\nConfiguration:");
+    println!("Channels: {}", targets.len());
+    if options.adaptive {
+        println!("Delete Delay: adaptive");
+    } else {
+        println!("Delete Delay: {}ms", options.delete_delay);
+    }
+    println!("Author ID: {}", author);
+    match imported_from {
+        Some(path) => println!("Messages from: {} ({} messages)", path.display(),
+            targets.iter().filter_map(|t| t.messages.as_ref()).map(Vec::len).sum::<usize>()),
+        None => println!("Listing strategy: {:?}", options.strategy),
     }
+    if let Some(before) = options.before {
+        println!("Only messages before: {}", before.to_rfc3339());
+    }
+    if let Some(after) = options.after {
+        println!("Only messages after: {}", after.to_rfc3339());
+    }
+    if options.dry_run {
+        println!("Dry run: no messages will be deleted");
+    }
+    if let Some(dir) = &options.archive_dir {
+        println!("Archive directory: {}", dir.display());
+    }
+    if options.export_only {
+        println!("Export only: messages will be archived but not deleted");
+    }
+}
 
-    async fn get_messages(&self, before: Option<&str>) -> Result<Vec<Message>> {
-        let mut url = format!(
-            "{}/channels/{}/messages?limit={}",
-            DISCORD_API, self.channel_id, MESSAGES_PER_REQUEST
+/// Uses `token` if one was given, otherwise the token saved by `login`, and looks up its owner.
+async fn load_token(token: Option<String>, token_file: &Path, api_url: &str) -> Result<(String, CurrentUser)> {
+    let token = match token {
+        Some(token) => token,
+        None => match credentials::load(token_file)? {
+            Some((token, store)) => {
+                info!("Using the token stored in {}", store);
+                token
+            }
+            None => return Err(anyhow!(
+                "No Discord token: run `discord_message_deleter login` to store one, or pass --token \
+                 (--dotenv reads DISCORD_TOKEN from a .env file)"
+            )),
+        },
+    };
+
+    let user = DiscordClient::validate_token(Transport::shared()?, api_url, &token).await?;
+    info!("Logged in as {} ({})", user.display_name(), user.id);
+    Ok((token, user))
+}
+
+/// The configured author ID, or the token owner if none is set. Only the token owner's
+/// messages can be deleted in DMs, so a different ID is worth a warning.
+fn resolve_author(configured: Option<String>, user: &CurrentUser) -> Result<String> {
+    let Some(author) = configured else {
+        return Ok(user.id.clone());
+    };
+    if !validate_snowflake(&author) {
+        return Err(anyhow!("Invalid author ID format: {}", author));
+    }
+    if author != user.id {
+        warn!(
+            "Author ID {} is not the owner of the token ({}, {}); in DMs only your own messages can be deleted",
+            author, user.display_name(), user.id
         );
+    }
+    Ok(author)
+}
+
+async fn run_delete(token: String, user: &CurrentUser, api_url: String, args: Box<DeleteArgs>, console: Console) -> Result<ExitCode> {
+    let Console { quiet, dashboard_log } = console;
+    let author = resolve_author(args.author.clone(), user)?;
+    if args.channels.is_empty() && args.guilds.is_empty() && args.from_package.is_none() && args.from_ids.is_none() {
+        return Err(anyhow!("Nothing to clean: pass --channel, --guild, --from-package or --from-ids, or add channels to the config profile"));
+    }
 
-        if let Some(message_id) = before {
-            url.push_str(&format!("&before={}", message_id));
+    if let (Some(before), Some(after)) = (args.before, args.after) {
+        if after >= before {
+            return Err(anyhow!("--after must be earlier than --before"));
         }
+    }
 
-        println!("Fetching messages from URL: {}", url);
-
-        loop {
-            let response = self.client.get(&url).send().await?;
-            
-            if response.status().is_success() {
-                let all_messages: Vec<Message> = response.json().await?;
-                println!("Total messages received from API: {}", all_messages.len());
-                
-                return Ok(all_messages);
-            } else if response.status() == StatusCode::TOO_MANY_REQUESTS {
-                let rate_limit: RateLimitResponse = response.json().await?;
-                println!("Rate limited! Waiting {} seconds...", rate_limit.retry_after);
-                sleep(Duration::from_secs_f64(rate_limit.retry_after)).await;
-                continue;
-            } else {
-                let status = response.status();
-                let text = response.text().await?;
-                println!("API Error URL: {}", url);
-                anyhow::bail!("Failed to get messages: {} - {}", status, text);
-            }
+    for channel_id in &args.channels {
+        if !validate_snowflake(channel_id) {
+            return Err(anyhow!("Invalid channel ID format: {}", channel_id));
+        }
+        let accessible = DiscordClient::validate_channel(Transport::shared()?, &api_url, &token, channel_id).await
+            .with_context(|| format!("Failed to check channel {}", channel_id))?;
+        if !accessible {
+            return Err(anyhow!("Channel {} not found or no access", channel_id));
         }
     }
 
-    async fn delete_message(&self, message_id: &str) -> Result<()> {
-        let mut backoff = 1.0; 
-        let max_backoff = 30.0; 
-        
-        let url = format!(
-            "{}/channels/{}/messages/{}",
-            DISCORD_API, self.channel_id, message_id
-        );
-    
-        loop {
-            let response = self.client.delete(&url).send().await?;
-            let status = response.status();
-            
-            if status.is_success() {
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.total_deleted += 1;
-                }
-                println!("Deleted message {}", message_id);
-                return Ok(());
-            } else if status == StatusCode::TOO_MANY_REQUESTS {
-                let rate_limit: RateLimitResponse = response.json().await?;
-                let wait_time = f64::max(rate_limit.retry_after, backoff);
-                println!("Rate limited! Waiting {} seconds before retrying...", wait_time);
-                sleep(Duration::from_secs_f64(wait_time)).await;
-                backoff = f64::min(backoff * 2.0, max_backoff);
-                continue;
-            } else if status == StatusCode::NOT_FOUND {
-                println!("Message {} not found (already deleted or too old)", message_id);
-                return Ok(());
-            } else if status == StatusCode::FORBIDDEN {
-                println!("No permission to delete message {}", message_id);
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.total_failed += 1;
-                }
-                return Ok(());
-            } else {
-                let text = response.text().await?;
-                println!("Error deleting message {}: {} - {}", message_id, status, text);
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.total_failed += 1;
-                }
-                
-                if status.is_server_error() {
-                    println!("Server error, retrying after backoff...");
-                    sleep(Duration::from_secs_f64(backoff)).await;
-                    backoff = f64::min(backoff * 2.0, max_backoff);
-                    continue;
+    let imported = match (&args.from_package, &args.from_ids) {
+        (Some(dir), _) => Some(import::read_data_package(dir)
+            .with_context(|| format!("Failed to read data package {}", dir.display()))?),
+        (None, Some(path)) => Some(import::read_id_list(path)?),
+        (None, None) => None,
+    };
+
+    let mut targets: Vec<Target> = match imported {
+        Some(channels) => channels.into_iter()
+            .filter(|c| args.channels.is_empty() || args.channels.contains(&c.channel_id))
+            .map(|c| Target {
+                label: c.name.clone().unwrap_or_else(|| c.channel_id.clone()),
+                messages: Some(c.to_messages(&author)),
+                channel_id: c.channel_id,
+            })
+            .collect(),
+        None => args.channels.iter()
+            .map(|id| Target::channel(id.clone(), id.clone()))
+            .collect(),
+    };
+
+    if !args.guilds.is_empty() {
+        for guild_id in &args.guilds {
+            if !validate_snowflake(guild_id) {
+                return Err(anyhow!("Invalid guild ID format: {}", guild_id));
+            }
+
+            let channels = guild::list_guild_channels(Transport::shared()?, &token, &api_url, guild_id).await
+                .with_context(|| format!("Failed to list channels of guild {}", guild_id))?;
+            info!(%guild_id, channels = channels.len(), "Found channels and threads");
+
+            for channel in channels {
+                if !targets.iter().any(|t| t.channel_id == channel.id) {
+                    targets.push(Target::channel(channel.id.clone(), channel.display_name()));
                 }
-                
-                return Ok(());
             }
         }
     }
 
-    async fn process_messages(&self, rx: mpsc::Receiver<Message>) {
-        let mut rx = rx;
-        
-        while let Some(message) = rx.recv().await {
-            match self.delete_message(&message.id).await {
-                Ok(_) => (),
-                Err(e) => {
-                    println!("Error deleting message {}: {}", message.id, e);
-                    {
-                        let mut stats = self.stats.lock().unwrap();
-                        stats.total_failed += 1;
-                        stats.messages_in_process = stats.messages_in_process.saturating_sub(1);
-                    }
-                }
-            }
-            
-            {
-                let mut stats = self.stats.lock().unwrap();
-                stats.messages_in_process = stats.messages_in_process.saturating_sub(1);
-            }
-            
-            sleep(Duration::from_millis(self.delete_delay)).await;
+    if args.workers > 1 && !args.yes && !args.tui {
+        return Err(anyhow!("--workers above 1 needs --yes, prompts from parallel channels would interleave"));
+    }
+    RateLimiter::shared().set_budget(args.max_requests_per_second);
+
+    let dry_run_output = match &args.output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create dry-run output file {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            writeln!(writer, "message_id\ttimestamp\tcontent")?;
+            Some(Arc::new(Mutex::new(writer)))
         }
+        None => None,
+    };
+
+    let options = RunOptions {
+        delete_delay: args.delay,
+        adaptive: args.adaptive,
+        dry_run: args.dry_run,
+        checkpoint_dir: (!args.dry_run && !args.export_only).then(|| args.checkpoint_dir.clone()),
+        resume: args.resume,
+        before: args.before,
+        after: args.after,
+        filter: args.filters.message_filter(),
+        strategy: args.strategy.into(),
+        archive_dir: args.archive.clone(),
+        archive_formats: args.archive_format.iter().copied().map(Into::into).collect(),
+        attachments: args.download_attachments.then(|| AttachmentOptions {
+            max_size: args.max_attachment_size.saturating_mul(1024 * 1024),
+            concurrency: usize::from(args.attachment_concurrency),
+        }),
+        export_only: args.export_only,
+        retry_rounds: args.retry.retry_rounds,
+        page_size: args.page_size,
+    };
+
+    if !quiet {
+        print_configuration(&targets, &options, &author, args.from_package.as_ref().or(args.from_ids.as_ref()));
     }
 
-    async fn list_messages(&self, tx: mpsc::Sender<Message>) -> Result<()> {
-        let mut last_message_id: Option<String> = None;
-        let mut seen_message_ids = std::collections::HashSet::new();
-        let mut total_batches = 0;
-        let mut consecutive_empty = 0;
-        let mut total_found = 0;
-
-        loop {
-            loop {
-                let messages_in_process = {
-                    let stats = self.stats.lock().unwrap();
-                    stats.messages_in_process
-                };
-                
-                if messages_in_process == 0 {
-                    break;
-                }
-                
-                sleep(Duration::from_millis(500)).await;
-            }
+    if targets.is_empty() {
+        return Err(anyhow!("No channels to process"));
+    }
 
-            let all_messages = self.get_messages(last_message_id.as_deref()).await?;
-            total_batches += 1;
-            
-            if all_messages.is_empty() {
-                consecutive_empty += 1;
-                println!("\nEmpty Batch #{} (Attempt {} of 10)", total_batches, consecutive_empty);
-                
-                if consecutive_empty >= 10 {
-                    println!("\nNo messages found in the last 10 batches.");
-                    println!("Last checked message ID: {}", last_message_id.as_deref().unwrap_or("None"));
-                    println!("Would you like to continue searching older messages? (Y/n): ");
-                    if read_input("")?.to_lowercase() == "n" {
-                        println!("Search stopped by user.");
-                        break;
-                    } else {
-                        consecutive_empty = 0;
-                        println!("Continuing search...");
-                        continue;
-                    }
-                }
+    let single_channel = targets.len() == 1;
+    let workers = usize::from(args.workers).min(targets.len());
+    if workers > 1 && !quiet {
+        println!("Processing {} channels at a time", workers);
+    }
 
-                if last_message_id.is_some() {
-                    println!("Moving to older messages (before ID: {})...", last_message_id.as_ref().unwrap());
-                    sleep(Duration::from_millis(200)).await;
-                    continue;
-                } else {
-                    println!("No messages found and no message ID to paginate from. Stopping.");
-                    break;
+    let progress: Option<ProgressCallback> = dry_run_output.clone().map(|output| {
+        let callback: ProgressCallback = Arc::new(move |event: &Progress| {
+            if let Progress::WouldDelete(message) = event {
+                let mut output = output.lock().unwrap();
+                let line = format!("{}\t{}\t{}", message.id, message.timestamp.to_rfc3339(), message.preview(PREVIEW_LENGTH));
+                if let Err(e) = writeln!(output, "{}", line) {
+                    warn!(error = %e, "Could not write dry-run output");
                 }
             }
+        });
+        callback
+    });
+
+    let audit = if args.no_audit || options.dry_run || options.export_only {
+        None
+    } else {
+        let log = AuditLog::open(&args.audit_db)
+            .with_context(|| format!("Failed to open audit database {}", args.audit_db.display()))?;
+        let description = format!("delete {} channel(s) by {}", targets.len(), author);
+        let run = log.start_run(&description)?;
+        info!(run_id = run.id(), db = %args.audit_db.display(), "Recording deletions");
+        Some(run)
+    };
+
+    let control = dashboard_log.is_some().then(RunControl::new);
+    let dashboard = match (&control, dashboard_log) {
+        (Some(control), Some(log)) => Some(
+            Dashboard::start(control.clone(), log, targets.len(), options.dry_run)
+                .context("Failed to start the dashboard")?,
+        ),
+        _ => None,
+    };
+
+    let dry_run = options.dry_run;
+    let run = DeleteRun {
+        token,
+        api_url,
+        author_id: author,
+        options,
+        progress,
+        audit,
+        interactive: !args.yes && dashboard.is_none(),
+        quiet,
+        control,
+        dashboard: dashboard.as_ref().map(Dashboard::sender),
+    };
+    let jobs: Vec<_> = targets.into_iter()
+        .enumerate()
+        .map(|(index, target)| process_channel(&run, index, target))
+        .collect();
+    let mut results = stream::iter(jobs).buffered(workers);
+    let mut summaries = Vec::new();
+    while let Some(result) = results.next().await {
+        let summary = result?;
+        let fatal = summary.fatal;
+        summaries.push(summary);
+        if fatal {
+            error!("Stopping: the last error affects every channel");
+            break;
+        }
+        if run.control.as_ref().is_some_and(RunControl::is_stopped) {
+            warn!("Stopped by user");
+            break;
+        }
+    }
+    drop(results);
+    if let Some(dashboard) = dashboard {
+        dashboard.finish().context("Failed to close the dashboard")?;
+    }
 
-            if let Some(last) = all_messages.last() {
-                last_message_id = Some(last.id.clone());
-            }
+    if let Some(output) = &dry_run_output {
+        output.lock().unwrap().flush().context("Failed to write dry-run output")?;
+    }
+    if let Some(audit) = &run.audit {
+        audit.finish()?;
+    }
+    let failed = summaries.iter_mut().flat_map(|s| std::mem::take(&mut s.retry_later)).collect();
+    save_retry_queue(&args.retry.retry_file, failed, run.quiet)?;
 
-            let user_messages: Vec<_> = all_messages.into_iter()
-                .filter(|m| m.author.id == self.author_id)
-                .collect();
+    if single_channel {
+        if let Some(error) = &summaries[0].error {
+            return Err(anyhow!("{}", error));
+        }
+    }
 
-            let batch_user_messages = user_messages.len();
-            total_found += batch_user_messages;
+    if summaries.len() > 1 || run.dashboard.is_some() || run.quiet {
+        print_run_summary(&summaries, dry_run);
+    }
 
-            println!("\nBatch Information:");
-            println!("Batch #{}", total_batches);
-            println!("Found {} user messages to delete (Total found: {})", batch_user_messages, total_found);
+    let failed = summaries.iter().any(|s| s.failed > 0 || s.error.is_some());
+    if failed {
+        Ok(ExitCode::from(EXIT_PARTIAL_FAILURE))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
 
-            if batch_user_messages > 0 {
-                consecutive_empty = 0;
-                
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.messages_in_process = batch_user_messages;
-                }
+async fn run_info(token: String, api_url: &str, channel_id: String) -> Result<ExitCode> {
+    if !validate_snowflake(&channel_id) {
+        return Err(anyhow!("Invalid channel ID format: {}", channel_id));
+    }
 
-                for message in user_messages {
-                    if seen_message_ids.contains(&message.id) {
-                        println!("Skipping already processed message: {}", message.id);
-                        continue;
-                    }
+    let info = DiscordClient::get_channel_info(Transport::shared()?, api_url, &token, &channel_id).await?;
+    print_channel_info(&info);
+    Ok(ExitCode::SUCCESS)
+}
 
-                    seen_message_ids.insert(message.id.to_string());
-                    if tx.send(message).await.is_err() {
-                        println!("Receiver has been dropped, stopping message listing");
-                        return Ok(());
-                    }
-                }
-            } else {
-                consecutive_empty += 1;
-                println!("Empty Batch #{} (Attempt {} of 10)", total_batches, consecutive_empty);
-                
-                if total_batches == 1 {
-                    println!("\nNo messages found from you in the first 100 messages.");
-                    println!("Would you like to search older messages? This might take longer.");
-                    if read_input("Continue searching? (Y/n): ")?.to_lowercase() == "n" {
-                        println!("Search aborted by user.");
-                        break;
-                    }
-                    consecutive_empty = 0;
-                    continue;
-                }
-                
-                if consecutive_empty >= 10 {
-                    println!("\nNo user messages found in the last 10 batches.");
-                    println!("Last checked message ID: {}", last_message_id.as_deref().unwrap_or("None"));
-                    println!("Would you like to continue searching older messages? (Y/n): ");
-                    if read_input("")?.to_lowercase() == "n" {
-                        println!("Search stopped by user.");
-                        break;
-                    } else {
-                        consecutive_empty = 0;
-                        println!("Continuing search...");
-                        continue;
-                    }
-                }
-            }
-            
-            {
-                let stats = self.stats.lock().unwrap();
-                println!(
-                    "\nOverall Progress:");
-                println!("Successfully deleted: {}", stats.total_deleted);
-                println!("Failed to delete: {}", stats.total_failed);
-                println!("Messages in process: {}", stats.messages_in_process);
-                println!("Total batches checked: {}", total_batches);
-                println!("Total messages found: {}", total_found);
-                
-                if stats.total_deleted + stats.total_failed >= total_found && 
-                   consecutive_empty >= 10 && 
-                   total_found > 0 {
-                    println!("\nAll found messages have been processed.");
-                    println!("Last checked message ID: {}", last_message_id.as_deref().unwrap_or("None"));
-                    println!("Would you like to continue searching older messages? (Y/n): ");
-                    if read_input("")?.to_lowercase() == "n" {
-                        println!("Search stopped by user.");
-                        break;
-                    } else {
-                        consecutive_empty = 0;
-                        println!("Continuing search...");
-                    }
-                }
-            }
-            
-            sleep(Duration::from_millis(200)).await;
-        }
+/// Adds the deletions that kept failing to the retry queue file.
+fn save_retry_queue(path: &Path, failed: Vec<FailedDeletion>, quiet: bool) -> Result<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
 
-        Ok(())
-    }
-
-    async fn delete_all_messages(&self) -> Result<()> {
-        let (tx, rx) = mpsc::channel(100);
-        
-        let list_client = self.clone();
-        let process_client = self.clone();
-        
-        println!("\nStarting message search and deletion process...");
-        println!("This may take a while depending on the number of messages and rate limits.");
-        println!("The program will automatically stop when all messages are processed.");
-        
-        let list_handle = tokio::spawn(async move {
-            list_client.list_messages(tx).await
-        });
-        
-        let process_handle = tokio::spawn(async move {
-            process_client.process_messages(rx).await
-        });
-        
-        list_handle.await??;
-        process_handle.await?;
-        
-        let stats = self.stats.lock().unwrap();
-        let elapsed = stats.start_time.elapsed();
-        let minutes = elapsed.as_secs() / 60;
-        let seconds = elapsed.as_secs() % 60;
-        
-        println!("\nOperation Complete!");
-        println!("Successfully deleted: {}", stats.total_deleted);
-        println!("Failed to delete: {}", stats.total_failed);
-        println!("Total time elapsed: {}m {}s", minutes, seconds);
-        
-        Ok(())
-    }
-
-    async fn get_channel_info(token: &str, channel_id: &str) -> Result<ChannelInfo> {
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(token)?,
-        );
+    let mut queue = RetryQueue::load(path)?;
+    queue.extend(failed);
+    queue.save(path)?;
+    if !quiet {
+        println!("{} failed messages saved to {}; run `retry-failed` to try them again", queue.len(), path.display());
+    }
+    Ok(())
+}
 
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()?;
+async fn run_retry_failed(token: String, api_url: &str, args: RetryArgs, quiet: bool) -> Result<ExitCode> {
+    let path = &args.retry.retry_file;
+    let mut queue = RetryQueue::load(path)?;
+    if queue.is_empty() {
+        if !quiet {
+            println!("No failed messages in {}", path.display());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !quiet {
+        println!("Retrying {} failed messages from {}", queue.len(), path.display());
+    }
 
-        let response = client.get(&format!("{}/channels/{}", DISCORD_API, channel_id))
-            .send()
-            .await?;
+    let audit = if args.no_audit {
+        None
+    } else {
+        let log = AuditLog::open(&args.audit_db)
+            .with_context(|| format!("Failed to open audit database {}", args.audit_db.display()))?;
+        Some(log.start_run(&format!("retry {} failed message(s)", queue.len()))?)
+    };
+    let options = RunOptions {
+        retry_rounds: args.retry.retry_rounds,
+        ..RunOptions::default()
+    };
+
+    let mut summaries = Vec::new();
+    let mut groups = queue.drain_by_channel().into_iter();
+    for ((channel_id, author_id), deletions) in groups.by_ref() {
+        let mut builder = DiscordClient::builder(token.as_str())
+            .api_base(api_url)
+            .channel(channel_id.as_str())
+            .author(author_id)
+            .options(options.clone());
+        if let Some(audit) = &audit {
+            builder = builder.audit(audit.clone());
+        }
+        let discord = builder.build()?;
+
+        let result = discord.retry_deletions(deletions).await;
+        let stats = discord.stats();
+        let mut summary = ChannelSummary {
+            channel: channel_id,
+            deleted: stats.deleted,
+            failed: stats.failed,
+            skipped: stats.skipped,
+            system: stats.system,
+            matched: 0,
+            error: None,
+            fatal: false,
+            retry_later: discord.failed_deletions(),
+        };
+        if let Err(e) = result {
+            error!(channel = %summary.channel, error = %e, "Error retrying channel");
+            summary.error = Some(e.to_string());
+            summary.fatal = e.severity() == Severity::Fatal;
+        }
+        let fatal = summary.fatal;
+        summaries.push(summary);
+        if fatal {
+            error!("Stopping: the last error affects every channel");
+            break;
+        }
+    }
 
-        if response.status().is_success() {
-            Ok(response.json().await?)
+    // Channels not reached because of a fatal error stay queued as they were.
+    queue.extend(groups.flat_map(|(_, deletions)| deletions));
+    queue.extend(summaries.iter_mut().flat_map(|s| std::mem::take(&mut s.retry_later)));
+    queue.save(path)?;
+    if let Some(audit) = &audit {
+        audit.finish()?;
+    }
+
+    print_run_summary(&summaries, false);
+    if !quiet {
+        if queue.is_empty() {
+            println!("Every queued message was handled, {} removed", path.display());
         } else {
-            Err(anyhow!("Failed to get channel info"))
+            println!("{} messages still failing, kept in {}", queue.len(), path.display());
         }
     }
+
+    let failed = summaries.iter().any(|s| s.failed > 0 || s.error.is_some());
+    if failed {
+        Ok(ExitCode::from(EXIT_PARTIAL_FAILURE))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
 }
 
-impl Clone for DiscordClient {
-    fn clone(&self) -> Self {
-        Self {
-            client: self.client.clone(),
-            channel_id: self.channel_id.clone(),
-            author_id: self.author_id.clone(),
-            stats: Arc::clone(&self.stats),
-            delete_delay: self.delete_delay,
+fn run_history(args: HistoryArgs) -> Result<ExitCode> {
+    if !args.db.exists() {
+        return Err(anyhow!("Audit database {} not found", args.db.display()));
+    }
+    let log = AuditLog::open(&args.db)
+        .with_context(|| format!("Failed to open audit database {}", args.db.display()))?;
+
+    if args.runs {
+        let runs = log.runs(args.limit)?;
+        if runs.is_empty() {
+            println!("No runs recorded.");
+        }
+        for run in runs {
+            let finished = match run.finished_at {
+                Some(time) => time.to_rfc3339(),
+                None => "unfinished".to_string(),
+            };
+            println!(
+                "#{}\t{}\t{}\t{} deleted, {} failed, {} skipped\t{}",
+                run.id, run.started_at.to_rfc3339(), finished, run.deleted, run.failed, run.skipped, run.description
+            );
         }
+        return Ok(ExitCode::SUCCESS);
     }
-}
 
-fn validate_snowflake(id: &str) -> bool {
-    id.len() >= 17 && id.len() <= 20 && id.chars().all(|c| c.is_ascii_digit())
+    let query = RecordQuery {
+        run_id: args.run,
+        channel_id: args.channel,
+        outcome: args.outcome,
+        limit: args.limit,
+    };
+    let records = log.records(&query)?;
+    if records.is_empty() {
+        println!("No messages recorded.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    println!("run\tchannel_id\tmessage_id\tmessage_timestamp\toutcome\tstatus\trecorded_at\terror");
+    for record in records {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            record.run_id,
+            record.channel_id,
+            record.message_id,
+            record.message_timestamp.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            record.outcome,
+            record.http_status.map(|s| s.to_string()).unwrap_or_default(),
+            record.recorded_at.to_rfc3339(),
+            record.error.unwrap_or_default(),
+        );
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn read_input(prompt: &str) -> Result<String> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    Ok(input.trim().to_string())
+async fn run_login(token: Option<String>, token_file: &Path, api_url: &str, args: LoginArgs) -> Result<ExitCode> {
+    let token = match token {
+        Some(token) => token,
+        None => rpassword::prompt_password("Discord token: ").context("Failed to read the token")?,
+    };
+    let token = token.trim().to_string();
+    let user = DiscordClient::validate_token(Transport::shared()?, api_url, &token).await?;
+
+    let store = credentials::save(&token, token_file, args.file)?;
+    println!("Token of {} ({}) stored in {}", user.display_name(), user.id, store);
+    if Path::new(".env").exists() {
+        println!("You can now remove DISCORD_TOKEN from your .env file.");
+    }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn read_number_input(prompt: &str, min: u64, max: u64, default: u64) -> Result<u64> {
-    let input = read_input(prompt)?;
-    if input.is_empty() {
-        return Ok(default);
+fn run_logout(token_file: &Path) -> Result<ExitCode> {
+    let removed = credentials::remove(token_file)?;
+    if removed.is_empty() {
+        println!("No stored token found");
     }
-
-    match input.parse::<u64>() {
-        Ok(num) if num >= min && num <= max => Ok(num),
-        Ok(_) => Err(anyhow!("Value must be between {} and {}", min, max)),
-        Err(_) => Err(anyhow!("Invalid number format")),
+    for store in removed {
+        println!("Removed the token from {}", store);
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenv().ok();
-
-    println!("Discord Message Deleter");
-    println!("----------------------");
-
-    let result = async {
-        let token = match env::var("DISCORD_TOKEN") {
-            Ok(token) => token,
-            Err(_) => return Err(anyhow!("DISCORD_TOKEN not found in .env file")),
-        };
-
-        if !DiscordClient::validate_token(&token).await? {
-            return Err(anyhow!("Invalid Discord token"));
+async fn run_command(
+    token: Option<String>,
+    token_file: &Path,
+    api_url: String,
+    command: Command,
+    console: Console,
+    config: &Config,
+    settings: &[Setting],
+) -> Result<ExitCode> {
+    match command {
+        Command::Delete(args) => {
+            let (token, user) = load_token(token, token_file, &api_url).await?;
+            run_delete(token, &user, api_url, args, console).await
         }
+        Command::Info { channel } => run_info(load_token(token, token_file, &api_url).await?.0, &api_url, channel).await,
+        Command::History(args) => run_history(args),
+        Command::RetryFailed(args) => run_retry_failed(load_token(token, token_file, &api_url).await?.0, &api_url, args, console.quiet).await,
+        Command::Login(args) => run_login(token, token_file, &api_url, args).await,
+        Command::Logout => run_logout(token_file),
+        Command::Config { command: ConfigCommand::Show(_) } => {
+            config::show(config, settings);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
 
-        let author_id = match env::var("AUTHOR_ID") {
-            Ok(id) if validate_snowflake(&id) => id,
-            Ok(_) => return Err(anyhow!("Invalid AUTHOR_ID format in .env file")),
-            Err(_) => return Err(anyhow!("AUTHOR_ID not found in .env file")),
-        };
-
-        let channel_id = loop {
-            let input = read_input("Enter channel ID: ")?;
-            if validate_snowflake(&input) {
-                if DiscordClient::validate_channel(&token, &input).await? {
-                    match DiscordClient::get_channel_info(&token, &input).await {
-                        Ok(info) => {
-                            println!("\nChannel Information:");
-                            println!("------------------");
-                            match info.channel_type {
-                                1 => {
-                                    println!("Type: Direct Message (DM)");
-                                    if let Some(recipients) = info.recipients {
-                                        for user in recipients {
-                                            println!("With User: {}#{}", user.username, user.discriminator);
-                                        }
-                                    }
-                                },
-                                3 => println!("Type: Group DM"),
-                                0 | 2 | 4 | 5 | 6 => {
-                                    println!("Type: Server Channel");
-                                    if let Some(name) = info.name {
-                                        println!("Channel Name: #{}", name);
-                                    }
-                                },
-                                _ => println!("Type: Unknown Channel Type"),
-                            }
-                            println!("------------------");
-                            break input;
-                        },
-                        Err(e) => {
-                            println!("Warning: Could not get channel details: {}", e);
-                            println!("Do you want to continue anyway? (y/N): ");
-                            if read_input("")?.to_lowercase() == "y" {
-                                break input;
-                            } else {
-                                continue;
-                            }
+async fn run_interactive(token: Option<String>, token_file: &Path, api_url: &str, profile_author: Option<String>) -> Result<ExitCode> {
+    let (token, user) = load_token(token, token_file, api_url).await?;
+    let author_id = resolve_author(env::var("AUTHOR_ID").ok().or(profile_author), &user)?;
+
+    let (channel_id, guild_id) = loop {
+        let input = read_input("Enter channel ID: ")?;
+        if validate_snowflake(&input) {
+            if DiscordClient::validate_channel(Transport::shared()?, api_url, &token, &input).await? {
+                match DiscordClient::get_channel_info(Transport::shared()?, api_url, &token, &input).await {
+                    Ok(info) => {
+                        print_channel_info(&info);
+                        break (input, info.guild_id);
+                    },
+                    Err(e) => {
+                        println!("Warning: Could not get channel details: {}", e);
+                        println!("Do you want to continue anyway? (y/N): ");
+                        if read_input("")?.to_lowercase() == "y" {
+                            break (input, None);
+                        } else {
+                            continue;
                         }
                     }
-                } else {
-                    println!("Channel not found or no access. Please try again.");
-                    continue;
                 }
+            } else {
+                println!("Channel not found or no access. Please try again.");
+                continue;
             }
-            println!("Invalid channel ID format. Please enter a valid Discord ID.");
-        };
-
-        let delete_delay = read_number_input(
-            &format!("Enter delay between message deletions ({}ms-{}ms, default 200ms): ",
+        }
+        println!("Invalid channel ID format. Please enter a valid Discord ID.");
+    };
+
+    let adaptive = read_input("Adjust the delay between deletions automatically? (Y/n): ")?
+        .to_lowercase() != "n";
+    let delete_delay = if adaptive {
+        0
+    } else {
+        read_number_input(
+            &format!("Enter extra delay between message deletions ({}ms-{}ms, default 0ms, rate limits are handled automatically): ",
                 MIN_DELETE_DELAY, MAX_DELETE_DELAY),
             MIN_DELETE_DELAY,
             MAX_DELETE_DELAY,
-            200,
-        )?;
-
-        println!("\nConfiguration:");
-        println!("Channel ID: {}", channel_id);
+            0,
+        )?
+    };
+
+    let strategy = if read_input("Use Discord search to find your messages? Faster in large channels (y/N): ")?
+        .to_lowercase() == "y"
+    {
+        ListingStrategy::Search
+    } else {
+        ListingStrategy::History
+    };
+
+    println!("\nConfiguration:");
+    println!("Channel ID: {}", channel_id);
+    if adaptive {
+        println!("Delete Delay: adaptive");
+    } else {
         println!("Delete Delay: {}ms", delete_delay);
-        println!("Author ID: {}", author_id);
-        
-        if read_input("\nContinue? (Y/n): ")?.to_lowercase() != "n" {
-            println!("\nStarting message deletion process...");
-            let discord = DiscordClient::new(token, channel_id, author_id, delete_delay)?;
-            discord.delete_all_messages().await?;
-        } else {
-            println!("Operation aborted by user.");
+    }
+    println!("Author ID: {}", author_id);
+    println!("Listing strategy: {:?}", strategy);
+
+    let checkpoint_dir = PathBuf::from(DEFAULT_CHECKPOINT_DIR);
+    let resume = match Checkpoint::load(&Checkpoint::path(&checkpoint_dir, &channel_id, &author_id))? {
+        Some(checkpoint) if !checkpoint.completed => {
+            println!("\nA previous run for this channel was interrupted after {} batches.", checkpoint.total_batches);
+            read_input("Resume from where it stopped? (Y/n): ")?.to_lowercase() != "n"
         }
+        _ => false,
+    };
 
-        Ok(())
-    }.await;
+    if read_input("\nContinue? (Y/n): ")?.to_lowercase() != "n" {
+        println!("\nStarting message deletion process...");
+        let options = RunOptions {
+            delete_delay,
+            adaptive,
+            dry_run: false,
+            checkpoint_dir: Some(checkpoint_dir),
+            resume,
+            before: None,
+            after: None,
+            filter: MessageFilter::everything(),
+            strategy,
+            archive_dir: None,
+            archive_formats: Vec::new(),
+            attachments: None,
+            export_only: false,
+            retry_rounds: 3,
+            page_size: MESSAGES_PER_REQUEST,
+        };
+        let audit = AuditLog::open(Path::new(DEFAULT_AUDIT_DB))
+            .with_context(|| format!("Failed to open audit database {}", DEFAULT_AUDIT_DB))?
+            .start_run(&format!("interactive delete in {}", channel_id))?;
+        let mut builder = DiscordClient::builder(token)
+            .api_base(api_url)
+            .channel(channel_id)
+            .author(author_id)
+            .options(options.clone())
+            .prompt(confirm)
+            .audit(audit.clone());
+        if let Some(guild_id) = guild_id {
+            builder = builder.guild(guild_id);
+        }
+        let discord = builder.build()?;
+        let result = discord.delete_all_messages().await;
+        audit.finish()?;
+        print_channel_result(&discord.stats(), &options, discord.channel_id());
+        save_retry_queue(Path::new(DEFAULT_RETRY_FILE), discord.failed_deletions(), false)?;
+        match result {
+            Ok(stats) if stats.failed > 0 => return Ok(ExitCode::from(EXIT_PARTIAL_FAILURE)),
+            Ok(_) => {}
+            Err(Error::Aborted) => println!("Operation aborted by user."),
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        println!("Operation aborted by user.");
+    }
 
-    if let Err(e) = result {
-        println!("\nError: {}", e);
-        println!("\nPress Enter to exit...");
-        read_input("")?;
-        return Err(e);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Logs how many requests the run sent and how they went.
+fn log_http_metrics() {
+    let Ok(transport) = Transport::shared() else {
+        return;
+    };
+    let metrics = transport.metrics();
+    if metrics.requests == 0 {
+        return;
+    }
+    let statuses = metrics.responses.iter()
+        .map(|(status, count)| format!("{}: {}", status, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!(
+        requests = metrics.requests,
+        statuses = %statuses,
+        retries = metrics.retries,
+        transport_errors = metrics.transport_errors,
+        timeouts = metrics.timeouts,
+        average_ms = metrics.average_latency().unwrap_or_default().as_millis() as u64,
+        "HTTP requests"
+    );
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut matches = Cli::command().get_matches();
+    if matches.get_flag("dotenv") {
+        // .env feeds the environment fallbacks of the arguments, so parse them again.
+        dotenv().ok();
+        matches = Cli::command().get_matches();
+    }
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (config, settings) = match config::resolve(&mut cli, &matches) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+
+    let dashboard_log = match &cli.command {
+        Some(Command::Delete(args)) if args.tui => Some(LogBuffer::default()),
+        _ => None,
+    };
+    if let Err(e) = logging::init(cli.quiet, cli.verbose, cli.log_file.as_deref(), dashboard_log.clone()) {
+        eprintln!("Error: {:#}", e);
+        return ExitCode::from(EXIT_FATAL);
     }
 
-    println!("\nPress Enter to exit...");
-    read_input("")?;
+    if let Err(e) = Transport::configure(&cli.http.options()) {
+        eprintln!("Error: {:#}", e);
+        return ExitCode::from(EXIT_FATAL);
+    }
 
-    Ok(())
-} 
\ No newline at end of file
+    if !cli.quiet {
+        println!("Discord Message Deleter");
+        println!("----------------------");
+    }
+
+    let token_file = cli.token_file.clone().unwrap_or_else(credentials::default_token_file);
+    let Some(command) = cli.command else {
+        let result = run_interactive(cli.token, &token_file, &cli.api_url, config.profile.author.clone()).await;
+        log_http_metrics();
+        if let Err(e) = &result {
+            println!("\nError: {}", e);
+        }
+        println!("\nPress Enter to exit...");
+        let _ = read_input("");
+        return result.unwrap_or(ExitCode::from(EXIT_FATAL));
+    };
+
+    let result = run_command(cli.token, &token_file, cli.api_url, command, Console { quiet: cli.quiet, dashboard_log }, &config, &settings).await;
+    log_http_metrics();
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("\nError: {}", e);
+            ExitCode::from(EXIT_FATAL)
+        }
+    }
+}
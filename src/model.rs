@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Message {
+    pub id: String,
+    pub author: Author,
+    #[serde(default)]
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<serde_json::Value>,
+    #[serde(rename = "type", default)]
+    pub kind: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_reference: Option<MessageReference>,
+    /// Every other field Discord sent (reactions, mentions, edits...), kept for archives.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Author {
+    pub id: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub global_name: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Attachment {
+    pub id: String,
+    pub filename: String,
+    pub url: String,
+    /// Size in bytes as reported by Discord; unknown for attachments rebuilt from a data package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MessageReference {
+    #[serde(default)]
+    pub message_id: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// System message types Discord refuses to delete: recipient changes, calls, channel name and
+/// icon changes, and thread starters. Every other type, pin and join notices included, can be
+/// deleted; newer undeletable types are caught by the 50021 error code instead.
+const UNDELETABLE_TYPES: [u8; 6] = [1, 2, 3, 4, 5, 21];
+
+impl Message {
+    /// Whether Discord lets the author delete this message.
+    pub fn is_deletable(&self) -> bool {
+        !UNDELETABLE_TYPES.contains(&self.kind)
+    }
+
+    /// Readable name of the message type, for logs.
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            0 => "message",
+            1 => "recipient added",
+            2 => "recipient removed",
+            3 => "call",
+            4 => "channel name change",
+            5 => "channel icon change",
+            6 => "pinned message notice",
+            7 => "member join",
+            8..=11 => "server boost",
+            12 => "channel follow",
+            14..=17 => "server discovery notice",
+            18 => "thread created",
+            19 => "reply",
+            20 | 23 => "command",
+            21 => "thread starter",
+            22 => "invite reminder",
+            24 => "auto moderation action",
+            _ => "system message",
+        }
+    }
+
+    /// ID of the message this one replies to, if any.
+    pub fn reply_to(&self) -> Option<&str> {
+        self.message_reference.as_ref()?.message_id.as_deref()
+    }
+
+    /// Content on a single line, cut to `max_chars` characters.
+    pub fn preview(&self, max_chars: usize) -> String {
+        let flattened = self.content.split_whitespace().collect::<Vec<_>>().join(" ");
+        if flattened.chars().count() > max_chars {
+            let truncated: String = flattened.chars().take(max_chars).collect();
+            format!("{}...", truncated)
+        } else {
+            flattened
+        }
+    }
+}
+
+impl Author {
+    pub fn display_name(&self) -> &str {
+        match &self.global_name {
+            Some(name) => name,
+            None if !self.username.is_empty() => &self.username,
+            None => &self.id,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChannelInfo {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub guild_id: Option<String>,
+    #[serde(rename = "type")]
+    pub channel_type: u8,
+    pub recipients: Option<Vec<User>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct User {
+    pub username: String,
+    pub discriminator: String,
+}
+
+/// The account a token belongs to, as returned by `/users/@me`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CurrentUser {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub global_name: Option<String>,
+    /// Public and internal account flags (staff, partner, verified developer...).
+    #[serde(default)]
+    pub flags: u64,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CurrentUser {
+    pub fn display_name(&self) -> &str {
+        self.global_name.as_deref().unwrap_or(&self.username)
+    }
+}
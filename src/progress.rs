@@ -0,0 +1,57 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::Message;
+
+/// Counters of one channel's run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub deleted: usize,
+    pub failed: usize,
+    /// Messages that were already gone or could not be deleted, and were left alone.
+    pub skipped: usize,
+    /// System messages (calls, recipient changes, thread starters...) that cannot be deleted and
+    /// were left alone.
+    pub system: usize,
+    /// Messages that matched in a dry run.
+    pub matched: usize,
+    pub archived: usize,
+    pub elapsed: Duration,
+}
+
+/// Events reported while a channel is cleaned up.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Progress {
+    /// A page of history or search results was checked.
+    BatchChecked {
+        batch: usize,
+        found: usize,
+        total_found: usize,
+    },
+    /// Best guess of how many messages the run will handle, when the listing knows it up front
+    /// (search results, data packages). Paging through the history does not.
+    Expected { total: usize },
+    /// A message would have been deleted, in a dry run.
+    WouldDelete(Box<Message>),
+    Archived { message_id: String },
+    Deleted { message_id: String },
+    /// A message could not be archived or deleted.
+    Failed { message_id: String, reason: String },
+    /// A message was left alone because of a skippable error.
+    Skipped { message_id: String, reason: String },
+    /// A system message (call, recipient change, thread starter...) was left alone because it
+    /// cannot be deleted.
+    Undeletable { message_id: String, kind: &'static str },
+    /// Deletions that failed during the run are tried again.
+    RetryRound { round: u32, pending: usize },
+    /// The run is over; carries the final counters.
+    Finished(StatsSnapshot),
+}
+
+/// Called with every [`Progress`] event. Shared between channels processed in parallel.
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Asks the user a yes/no question and returns the answer.
+///
+/// Clients without a prompt run unattended and answer every question with yes.
+pub type Prompt = Arc<dyn Fn(&str) -> bool + Send + Sync>;
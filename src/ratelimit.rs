@@ -0,0 +1,288 @@
+use reqwest::{header::HeaderMap, Method, Response, StatusCode};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::{error::{Error, Result}, pacing::AdaptivePacer, transport::Transport};
+
+/// Path segments whose following ID is a "major parameter": Discord keeps separate limits per
+/// channel, guild and webhook even when routes share a bucket.
+const MAJOR_PARAMETERS: [&str; 3] = ["channels", "guilds", "webhooks"];
+
+/// Waits shorter than this are not worth a log line.
+const QUIET_WAIT: Duration = Duration::from_secs(1);
+
+/// Wait used when a 429 response carries no usable `retry_after`.
+const DEFAULT_RETRY_AFTER: f64 = 1.0;
+
+/// Consecutive 429 responses after which [`send`] gives the request back to its caller as
+/// [`Error::RateLimited`].
+const MAX_RATE_LIMITED: u32 = 3;
+
+static SHARED: LazyLock<Arc<RateLimiter>> = LazyLock::new(|| Arc::new(RateLimiter::new()));
+
+#[derive(Debug, Deserialize)]
+struct RateLimitResponse {
+    retry_after: f64,
+    #[serde(default)]
+    global: bool,
+}
+
+/// A normalized API route, e.g. `DELETE /channels/123/messages/:id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Route {
+    key: String,
+    major: String,
+}
+
+impl Route {
+    pub fn new(method: &Method, url: &str) -> Self {
+        // Only the path counts, so the same route is recognized behind any API base URL.
+        let path = match url.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("", |start| &rest[start..]),
+            None => url,
+        };
+        let path = path.split("/api/v10").nth(1).unwrap_or(path);
+        let path = path.split('?').next().unwrap_or(path);
+
+        let mut normalized = Vec::new();
+        let mut major = Vec::new();
+        let mut previous = "";
+        for segment in path.split('/') {
+            let is_id = !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit());
+            if is_id && MAJOR_PARAMETERS.contains(&previous) {
+                major.push(segment);
+                normalized.push(segment);
+            } else if is_id {
+                normalized.push(":id");
+            } else {
+                normalized.push(segment);
+            }
+            previous = segment;
+        }
+
+        Self {
+            key: format!("{} {}", method, normalized.join("/")),
+            major: major.join("/"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    remaining: u32,
+    reset_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Bucket hash Discord reported for each route.
+    route_buckets: HashMap<String, String>,
+    /// Bucket state keyed by `<hash>:<major parameter>`.
+    buckets: HashMap<String, Bucket>,
+    global_reset: Option<Instant>,
+    /// Minimum spacing between any two requests, from the user's request budget.
+    budget_interval: Option<Duration>,
+    next_budget_slot: Option<Instant>,
+    status: RateLimitStatus,
+}
+
+/// What the limiter has done so far, for progress displays.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitStatus {
+    /// Requests that had to wait a second or more for their bucket, the global limit or the
+    /// request budget.
+    pub waits: usize,
+    pub total_wait: Duration,
+    /// 429 responses received.
+    pub rate_limited: usize,
+    /// End of the longest wait in progress, if a request is waiting right now.
+    pub waiting_until: Option<Instant>,
+}
+
+impl State {
+    fn bucket_key(&self, route: &Route) -> Option<String> {
+        self.route_buckets.get(&route.key).map(|hash| format!("{}:{}", hash, route.major))
+    }
+}
+
+/// Tracks Discord's per-route buckets and the global limit, so requests wait *before* they
+/// are sent instead of running into 429 responses.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The limiter of every client in this process that is not given its own through
+    /// [`ClientBuilder::rate_limiter`](crate::ClientBuilder::rate_limiter), and of the checks
+    /// made before a client exists.
+    pub fn shared() -> Arc<RateLimiter> {
+        Arc::clone(&SHARED)
+    }
+
+    /// Caps the request rate of every route together, so parallel workers share one budget.
+    pub fn set_budget(&self, requests_per_second: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.budget_interval = (requests_per_second > 0)
+            .then(|| Duration::from_secs_f64(1.0 / f64::from(requests_per_second)));
+    }
+
+    pub fn status(&self) -> RateLimitStatus {
+        let mut status = self.state.lock().unwrap().status;
+        status.waiting_until = status.waiting_until.filter(|until| *until > Instant::now());
+        status
+    }
+
+    /// Waits until a request on `route` is allowed and reserves one slot in its bucket.
+    pub async fn acquire(&self, route: &Route) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+
+                let wait = match (state.global_reset, state.next_budget_slot) {
+                    (Some(reset), _) if reset > now => Some(reset - now),
+                    (_, Some(slot)) if slot > now => Some(slot - now),
+                    _ => {
+                        state.global_reset = None;
+                        let key = state.bucket_key(route);
+                        let wait = match key.and_then(|key| state.buckets.get_mut(&key)) {
+                            Some(bucket) if bucket.reset_at <= now => None,
+                            Some(bucket) if bucket.remaining == 0 => Some(bucket.reset_at - now),
+                            Some(bucket) => {
+                                bucket.remaining -= 1;
+                                None
+                            }
+                            None => None,
+                        };
+                        if wait.is_none() {
+                            state.next_budget_slot = state.budget_interval.map(|interval| now + interval);
+                        }
+                        wait
+                    }
+                };
+                if let Some(wait) = wait.filter(|wait| *wait >= QUIET_WAIT) {
+                    let status = &mut state.status;
+                    status.waits += 1;
+                    status.total_wait += wait;
+                    status.waiting_until = status.waiting_until.max(Some(now + wait));
+                }
+                wait
+            };
+
+            match wait {
+                Some(wait) => {
+                    if wait >= QUIET_WAIT {
+                        info!(route = %route.key, wait_seconds = wait.as_secs_f64(), "Rate limit reached, waiting");
+                    }
+                    sleep(wait).await;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Records the rate-limit headers of a response.
+    pub fn update(&self, route: &Route, headers: &HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let Some(hash) = header("x-ratelimit-bucket") else {
+            return;
+        };
+        let remaining = header("x-ratelimit-remaining").and_then(|v| v.parse::<u32>().ok());
+        let reset_after = header("x-ratelimit-reset-after").and_then(|v| v.parse::<f64>().ok());
+
+        let mut state = self.state.lock().unwrap();
+        state.route_buckets.insert(route.key.clone(), hash.to_string());
+
+        if let (Some(remaining), Some(reset_after)) = (remaining, reset_after) {
+            let key = format!("{}:{}", hash, route.major);
+            state.buckets.insert(key, Bucket {
+                remaining,
+                reset_at: Instant::now() + Duration::from_secs_f64(reset_after),
+            });
+        }
+    }
+
+    /// Records a 429 response so every later request on the bucket (or all requests, for the
+    /// global limit) waits it out.
+    pub fn rate_limited(&self, route: &Route, retry_after: f64, global: bool) {
+        let reset_at = Instant::now() + Duration::from_secs_f64(retry_after.max(0.0));
+        let mut state = self.state.lock().unwrap();
+        state.status.rate_limited += 1;
+
+        if global {
+            state.global_reset = Some(reset_at);
+            return;
+        }
+
+        let key = state.bucket_key(route).unwrap_or_else(|| format!("{}:{}", route.key, route.major));
+        state.route_buckets.entry(route.key.clone()).or_insert_with(|| route.key.clone());
+        state.buckets.insert(key, Bucket { remaining: 0, reset_at });
+    }
+}
+
+/// Sends a request authorized with `token` through `transport`, waiting for its bucket in
+/// `limiter` first and retrying after 429s.
+///
+/// After [`MAX_RATE_LIMITED`] 429s in a row the last one is returned as
+/// [`Error::RateLimited`], so the caller's own retry limit applies. Every other status is returned to the caller unchanged. When a `pacer` is given it is told
+/// about every response so it can adjust the pace of the caller.
+pub async fn send(
+    transport: &Transport,
+    token: &str,
+    limiter: &RateLimiter,
+    pacer: Option<&AdaptivePacer>,
+    method: Method,
+    url: &str,
+) -> Result<Response> {
+    let route = Route::new(&method, url);
+    let mut rate_limited = 0;
+
+    loop {
+        limiter.acquire(&route).await;
+        let response = transport.send(method.clone(), url, token).await?;
+        limiter.update(&route, response.headers());
+
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            if let Some(pacer) = pacer {
+                pacer.on_success(response.headers());
+            }
+            return Ok(response);
+        }
+
+        let header = |name: &str| {
+            response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+        };
+        let global_header = header("x-ratelimit-global").is_some_and(|v| v == "true")
+            || header("x-ratelimit-scope").is_some_and(|v| v == "global");
+        let retry_header = header("retry-after").and_then(|v| v.parse::<f64>().ok());
+
+        let body: Option<RateLimitResponse> = response.json().await.ok();
+        let global = global_header || body.as_ref().is_some_and(|b| b.global);
+        let retry_after = body.map(|b| b.retry_after)
+            .or(retry_header)
+            .unwrap_or(DEFAULT_RETRY_AFTER);
+
+        warn!(route = %route.key, retry_after, global, "Rate limited");
+        limiter.rate_limited(&route, retry_after, global);
+        if let Some(pacer) = pacer {
+            pacer.on_rate_limited(retry_after);
+        }
+
+        rate_limited += 1;
+        if rate_limited >= MAX_RATE_LIMITED {
+            return Err(Error::RateLimited { retry_after });
+        }
+    }
+}
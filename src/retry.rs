@@ -0,0 +1,279 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::{
+    audit::Outcome,
+    error::{Error, IoContext, Result, Severity},
+    progress::{Progress, StatsSnapshot},
+    DiscordClient, Message,
+};
+
+/// Default retry queue file, relative to the working directory.
+pub const DEFAULT_RETRY_FILE: &str = "failed_messages.json";
+
+/// Pause before the first retry round; doubled after every round.
+const ROUND_DELAY_SECONDS: f64 = 5.0;
+const MAX_ROUND_DELAY_SECONDS: f64 = 60.0;
+
+/// A deletion that kept failing, saved so it can be tried again later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedDeletion {
+    pub channel_id: String,
+    pub author_id: String,
+    pub message_id: String,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub reason: String,
+    pub status: Option<u16>,
+    /// Delete requests sent so far, not counting the backoff retries of a single request.
+    pub attempts: u32,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Failed deletions of earlier runs, stored as JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetryQueue {
+    pub deletions: Vec<FailedDeletion>,
+}
+
+impl RetryQueue {
+    /// Loads the queue, returning an empty one if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .io_context(|| format!("Failed to read retry queue {}", path.display()))?;
+        serde_json::from_str(&data)
+            .map_err(|source| Error::InvalidRetryQueue { path: path.to_path_buf(), source })
+    }
+
+    /// Writes the queue through a temporary file. An empty queue removes the file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if self.deletions.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)
+                    .io_context(|| format!("Failed to remove retry queue {}", path.display()))?;
+            }
+            return Ok(());
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)
+            .io_context(|| format!("Failed to write retry queue {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .io_context(|| format!("Failed to save retry queue {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deletions.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.deletions.len()
+    }
+
+    /// Adds deletions, replacing older entries for the same message.
+    pub fn extend(&mut self, deletions: impl IntoIterator<Item = FailedDeletion>) {
+        for deletion in deletions {
+            self.deletions.retain(|d| d.message_id != deletion.message_id);
+            self.deletions.push(deletion);
+        }
+    }
+
+    /// Takes every deletion out of the queue, grouped by channel and author.
+    pub fn drain_by_channel(&mut self) -> BTreeMap<(String, String), Vec<FailedDeletion>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for deletion in self.deletions.drain(..) {
+            groups.entry((deletion.channel_id.clone(), deletion.author_id.clone()))
+                .or_default()
+                .push(deletion);
+        }
+        groups
+    }
+}
+
+/// A deletion waiting for the retry rounds at the end of the run.
+pub(crate) struct Deferred {
+    deletion: FailedDeletion,
+    error: Error,
+}
+
+/// Errors that may go away later: missing permissions, unexpected statuses, and temporary
+/// failures that outlasted the backoff of a single request.
+fn worth_retrying(error: &Error) -> bool {
+    matches!(error, Error::MissingAccess { .. } | Error::Api { .. }) || error.severity() == Severity::Retryable
+}
+
+impl DiscordClient {
+    fn failed_deletion(&self, message_id: &str, timestamp: Option<DateTime<Utc>>, error: &Error) -> FailedDeletion {
+        FailedDeletion {
+            channel_id: self.channel_id.clone(),
+            author_id: self.author_id.clone(),
+            message_id: message_id.to_string(),
+            timestamp,
+            reason: error.to_string(),
+            status: error.status().map(|status| status.as_u16()),
+            attempts: 1,
+            failed_at: Utc::now(),
+        }
+    }
+
+    /// Queues a failed deletion for the end of the run if the error may go away; otherwise
+    /// hands it back.
+    pub(crate) fn defer(&self, message: &Message, error: Error) -> Option<Error> {
+        if !worth_retrying(&error) {
+            return Some(error);
+        }
+
+        warn!(%error, "Failed to delete message, will retry at the end of the run");
+        let deletion = self.failed_deletion(&message.id, Some(message.timestamp), &error);
+        self.deferred.lock().unwrap().push(Deferred { deletion, error });
+        None
+    }
+
+    /// Deletes one queued message and records the outcome. Returns it again if it should be
+    /// retried in the next round.
+    async fn retry_one(&self, mut deletion: FailedDeletion) -> Result<Option<Deferred>> {
+        match self.delete_message(&deletion.message_id).await {
+            Ok(()) => {
+                self.record_deleted(&deletion.message_id, deletion.timestamp)?;
+                Ok(None)
+            }
+            Err(error) if worth_retrying(&error) => {
+                deletion.attempts += 1;
+                deletion.reason = error.to_string();
+                deletion.status = error.status().map(|status| status.as_u16());
+                deletion.failed_at = Utc::now();
+                Ok(Some(Deferred { deletion, error }))
+            }
+            Err(error) => {
+                if error.severity() == Severity::Fatal {
+                    // The error says nothing about this message, so it stays queued.
+                    self.failed.lock().unwrap().push(deletion.clone());
+                }
+                self.record_error(&deletion.message_id, deletion.timestamp, error)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Retries the deletions [`process_messages`](Self::process_messages) queued, with a growing
+    /// pause before every round. Whatever still fails afterwards is counted as failed and kept
+    /// for [`failed_deletions`](Self::failed_deletions).
+    ///
+    /// [`delete_all_messages`](Self::delete_all_messages) calls this itself.
+    pub async fn retry_deferred(&self) -> Result<()> {
+        let mut delay = ROUND_DELAY_SECONDS;
+
+        for round in 1..=self.options.retry_rounds {
+            if self.cancelled() {
+                break;
+            }
+            let pending = std::mem::take(&mut *self.deferred.lock().unwrap());
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            info!(round, pending = pending.len(), delay_seconds = delay, "Retrying failed deletions");
+            self.notify(Progress::RetryRound { round, pending: pending.len() });
+            self.control.sleep(self.generation, Duration::from_secs_f64(delay)).await;
+            delay = f64::min(delay * 2.0, MAX_ROUND_DELAY_SECONDS);
+
+            let span = info_span!("retry", round);
+            let mut pending = pending.into_iter();
+            for item in pending.by_ref() {
+                self.control.wait_while_paused(self.generation).await;
+                if self.cancelled() {
+                    self.deferred.lock().unwrap().push(item);
+                    continue;
+                }
+                let message_id = item.deletion.message_id.clone();
+                let result = self.retry_one(item.deletion)
+                    .instrument(info_span!(parent: &span, "delete", %message_id))
+                    .await;
+                match result {
+                    Ok(Some(again)) => self.deferred.lock().unwrap().push(again),
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.deferred.lock().unwrap().extend(pending);
+                        self.give_up_deferred()?;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        self.give_up_deferred()
+    }
+
+    /// Counts every queued deletion as failed without retrying it, e.g. when the run stops.
+    pub fn give_up_deferred(&self) -> Result<()> {
+        let remaining = std::mem::take(&mut *self.deferred.lock().unwrap());
+        for Deferred { deletion, error } in remaining {
+            self.give_up(deletion, &error)?;
+        }
+        Ok(())
+    }
+
+    fn give_up(&self, deletion: FailedDeletion, error: &Error) -> Result<()> {
+        let reason = error.to_string();
+        error!(message_id = %deletion.message_id, attempts = deletion.attempts, %reason, "Giving up on message");
+        self.audit(&deletion.message_id, deletion.timestamp, Outcome::Failed, Some(error))?;
+        self.stats.lock().unwrap().total_failed += 1;
+        self.notify(Progress::Failed { message_id: deletion.message_id.clone(), reason });
+        self.failed.lock().unwrap().push(deletion);
+        Ok(())
+    }
+
+    /// Deletes the given messages of this client's channel, e.g. the ones a previous run saved
+    /// to its retry queue, then runs the usual retry rounds for those that fail again.
+    pub async fn retry_deletions(&self, deletions: Vec<FailedDeletion>) -> Result<StatsSnapshot> {
+        let span = info_span!("channel", channel_id = %self.channel_id);
+        async {
+            info!(messages = deletions.len(), "Retrying failed deletions of an earlier run");
+            self.notify(Progress::Expected { total: deletions.len() });
+            let mut deletions = deletions.into_iter();
+            for deletion in deletions.by_ref() {
+                if self.check_control().await.is_err() {
+                    self.failed.lock().unwrap().push(deletion);
+                    break;
+                }
+                let message_id = deletion.message_id.clone();
+                let result = self.retry_one(deletion)
+                    .instrument(info_span!("delete", %message_id))
+                    .await;
+                match result {
+                    Ok(Some(again)) => {
+                        warn!(%message_id, error = %again.error, "Failed to delete message, will retry");
+                        self.deferred.lock().unwrap().push(again);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        // Nothing else would work either; keep the rest queued for a later run.
+                        self.failed.lock().unwrap().extend(deletions);
+                        self.give_up_deferred()?;
+                        return Err(e);
+                    }
+                }
+            }
+            // Deletions not reached because the run was stopped stay queued as they were.
+            self.failed.lock().unwrap().extend(deletions);
+            self.retry_deferred().await?;
+
+            let stats = self.stats();
+            self.notify(Progress::Finished(stats));
+            Ok(stats)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Deletions that were still failing when the run ended.
+    pub fn failed_deletions(&self) -> Vec<FailedDeletion> {
+        self.failed.lock().unwrap().clone()
+    }
+}
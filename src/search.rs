@@ -0,0 +1,183 @@
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::{sync::mpsc, time::sleep};
+use tracing::{debug, info, info_span, Instrument};
+
+use crate::{client::read_json, error::Result, progress::Progress, snowflake::snowflake_from_timestamp, DiscordClient, Message};
+
+/// Seconds to wait when Discord reports the search index is not ready but omits `retry_after`.
+const INDEX_RETRY_SECONDS: f64 = 2.0;
+
+/// How messages are found before they are deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListingStrategy {
+    /// Page through the whole channel history, 100 messages at a time
+    #[default]
+    History,
+    /// Ask Discord's search index for messages by the author
+    Search,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    total_results: usize,
+    /// Each result is a group of messages: the hit, plus surrounding context on older API versions.
+    #[serde(default)]
+    messages: Vec<Vec<Message>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexNotReady {
+    #[serde(default)]
+    retry_after: Option<f64>,
+}
+
+impl DiscordClient {
+    fn search_url(&self, offset: usize) -> String {
+        let mut url = match &self.guild_id {
+            Some(guild_id) => format!(
+                "{}/guilds/{}/messages/search?author_id={}&channel_id={}&include_nsfw=true",
+                self.api_base, guild_id, self.author_id, self.channel_id
+            ),
+            None => format!(
+                "{}/channels/{}/messages/search?author_id={}",
+                self.api_base, self.channel_id, self.author_id
+            ),
+        };
+
+        if let Some(before) = self.options.before {
+            url.push_str(&format!("&max_id={}", snowflake_from_timestamp(before)));
+        }
+        if let Some(after) = self.options.after {
+            url.push_str(&format!("&min_id={}", snowflake_from_timestamp(after)));
+        }
+        if offset > 0 {
+            url.push_str(&format!("&offset={}", offset));
+        }
+        url
+    }
+
+    async fn search_messages(&self, offset: usize) -> Result<SearchResponse> {
+        let url = self.search_url(offset);
+        debug!(%url, "Searching messages");
+
+        loop {
+            let response = self.request(Method::GET, &url).await?;
+
+            if response.status() == StatusCode::ACCEPTED {
+                let not_ready: IndexNotReady = read_json(response).await?;
+                let wait = not_ready.retry_after.unwrap_or(INDEX_RETRY_SECONDS);
+                info!(wait_seconds = wait, "Search index is not ready yet, waiting");
+                sleep(Duration::from_secs_f64(wait)).await;
+                continue;
+            }
+
+            return read_json(response).await;
+        }
+    }
+
+    /// Lists messages through the search index instead of paging the full history.
+    ///
+    /// Deleted messages drop out of the results, so the offset only advances past results that
+    /// were kept (filtered out or already seen). In dry-run mode nothing is deleted and the
+    /// offset advances by the full page.
+    pub(crate) async fn list_search(&self, tx: mpsc::Sender<Message>) -> Result<()> {
+        let mut checkpoint = self.load_checkpoint()?;
+        let mut offset = checkpoint.search_offset;
+        let mut seen_message_ids = checkpoint.seen_message_ids.clone();
+        let mut total_batches = checkpoint.total_batches;
+        let mut total_found = checkpoint.total_found;
+        let known_deleted = self.known_deleted()?;
+        let mut announced_total = false;
+
+        loop {
+            self.wait_for_batch().await;
+            if self.cancelled() {
+                info!("Stopped, the checkpoint keeps the last completed page");
+                return Ok(());
+            }
+
+            checkpoint.search_offset = offset;
+            checkpoint.seen_message_ids = seen_message_ids.clone();
+            checkpoint.total_batches = total_batches;
+            checkpoint.total_found = total_found;
+            self.save_checkpoint(&mut checkpoint)?;
+
+            let page = self.search_messages(offset)
+                .instrument(info_span!("batch", batch = total_batches + 1))
+                .await?;
+            total_batches += 1;
+
+            if page.messages.is_empty() {
+                info!("No more search results");
+                break;
+            }
+
+            if !announced_total {
+                announced_total = true;
+                self.notify(Progress::Expected { total: total_found + page.total_results });
+            }
+
+            let page_messages: Vec<Message> = page.messages.into_iter().flatten().collect();
+            let page_len = page_messages.len();
+            let user_messages: Vec<_> = page_messages.into_iter()
+                .filter(|m| self.wanted(m, &seen_message_ids, &known_deleted))
+                .collect();
+
+            let batch_user_messages = user_messages.len();
+            total_found += batch_user_messages;
+
+            info!(
+                page = total_batches,
+                offset,
+                total_results = page.total_results,
+                found = batch_user_messages,
+                total_found,
+                "Search page checked"
+            );
+            self.print_pace();
+            self.notify(Progress::BatchChecked { batch: total_batches, found: batch_user_messages, total_found });
+
+            {
+                let mut stats = self.stats.lock().unwrap();
+                stats.messages_in_process = batch_user_messages;
+            }
+
+            for message in user_messages {
+                seen_message_ids.insert(message.id.to_string());
+                if tx.send(message).await.is_err() {
+                    debug!("Receiver has been dropped, stopping message listing");
+                    return Ok(());
+                }
+            }
+
+            // Deleted messages drop out of the results, so only the ones left behind move the
+            // offset; in dry and export-only runs nothing is deleted.
+            offset += if self.options.dry_run || self.options.export_only {
+                page_len
+            } else {
+                page_len.saturating_sub(batch_user_messages)
+            };
+
+            if offset >= page.total_results {
+                info!("Reached the end of the search results");
+                break;
+            }
+
+            sleep(Duration::from_millis(500)).await;
+        }
+
+        self.wait_for_batch().await;
+
+        checkpoint.search_offset = offset;
+        checkpoint.seen_message_ids = seen_message_ids;
+        checkpoint.total_batches = total_batches;
+        checkpoint.total_found = total_found;
+        checkpoint.completed = true;
+        self.save_checkpoint(&mut checkpoint)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+/// Milliseconds between the Unix epoch and the Discord epoch (2015-01-01T00:00:00Z).
+const DISCORD_EPOCH_MS: u64 = 1_420_070_400_000;
+
+pub fn validate_snowflake(id: &str) -> bool {
+    (17..=20).contains(&id.len()) && id.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Returns the smallest snowflake that could have been created at `time`.
+///
+/// Any message ID lower than the result was sent before `time`, which makes it usable as a
+/// `before`/`after` cursor for the messages endpoint.
+pub fn snowflake_from_timestamp(time: DateTime<Utc>) -> u64 {
+    let ms = u64::try_from(time.timestamp_millis()).unwrap_or(0);
+    ms.saturating_sub(DISCORD_EPOCH_MS) << 22
+}
+
+/// Returns the time a snowflake was created at, or `None` if `id` is not a number.
+pub fn timestamp_from_snowflake(id: &str) -> Option<DateTime<Utc>> {
+    let ms = (id.parse::<u64>().ok()? >> 22) + DISCORD_EPOCH_MS;
+    DateTime::from_timestamp_millis(i64::try_from(ms).ok()?)
+}
+
+/// Parses a `--before`/`--after` argument, either a plain `YYYY-MM-DD` date (midnight UTC) or
+/// a full RFC 3339 timestamp.
+pub fn parse_date(input: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+
+    DateTime::parse_from_rfc3339(input)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD or an RFC 3339 timestamp", input))
+}
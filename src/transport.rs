@@ -0,0 +1,240 @@
+//! The HTTP transport every request goes through: connection pools for API calls and
+//! attachment downloads with timeouts, a User-Agent, an optional proxy and CA bundle, retries on
+//! transport errors and request counters.
+//!
+//! API calls use [`Transport::shared`] unless a client is given its own transport with
+//! [`ClientBuilder::transport`](crate::ClientBuilder::transport); its archive downloads go
+//! through the same one. Calls made without a client, such as
+//! [`DiscordClient::validate_token`](crate::DiscordClient::validate_token) and
+//! [`guild::list_guild_channels`](crate::guild::list_guild_channels), take the transport as an
+//! argument. Call [`Transport::configure`] before the first request to change the shared
+//! transport's [`HttpOptions`].
+
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Certificate, Client, Method, Proxy, Response,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+use crate::error::{Error, IoContext, Result};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Retries of a request that failed before a response arrived, see [`HttpOptions::retries`].
+pub const DEFAULT_TRANSPORT_RETRIES: u32 = 3;
+pub const USER_AGENT: &str = concat!("discord_message_deleter/", env!("CARGO_PKG_VERSION"));
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+static SHARED: OnceLock<Transport> = OnceLock::new();
+
+/// Settings of a [`Transport`].
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Longest wait for a connection to be established.
+    pub connect_timeout: Duration,
+    /// Longest an API request may take from sending it to reading the last byte of the
+    /// response. Attachment downloads are not limited, as large files may take longer.
+    pub timeout: Duration,
+    /// Retries of requests that failed to connect, or of `GET` requests that timed out or lost
+    /// their connection.
+    pub retries: u32,
+    /// `http://`, `https://` or `socks5://` proxy for every request. Without one the
+    /// `HTTPS_PROXY`/`ALL_PROXY` environment variables are used.
+    pub proxy: Option<String>,
+    /// PEM file of extra root certificates to trust, e.g. for an intercepting proxy.
+    pub ca_bundle: Option<PathBuf>,
+    pub user_agent: String,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_TRANSPORT_RETRIES,
+            proxy: None,
+            ca_bundle: None,
+            user_agent: USER_AGENT.to_string(),
+        }
+    }
+}
+
+/// Counters of the requests sent through a [`Transport`].
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// Requests sent, retries included.
+    pub requests: u64,
+    /// Responses received, by status code.
+    pub responses: BTreeMap<u16, u64>,
+    /// Requests that got no response: connection, TLS and proxy errors and timeouts.
+    pub transport_errors: u64,
+    /// Transport errors that were timeouts.
+    pub timeouts: u64,
+    /// Requests sent again after a transport error.
+    pub retries: u64,
+    /// Time spent waiting for response headers, over all requests.
+    pub total_latency: Duration,
+}
+
+impl Metrics {
+    pub fn average_latency(&self) -> Option<Duration> {
+        u32::try_from(self.requests).ok()
+            .filter(|&requests| requests > 0)
+            .map(|requests| self.total_latency / requests)
+    }
+}
+
+/// An HTTP client shared by every request, see the [module docs](self).
+///
+/// Cloning is cheap; clones share the connection pool and metrics.
+#[derive(Debug, Clone)]
+pub struct Transport {
+    client: Client,
+    /// Same settings as `client`, without the total timeout.
+    downloads: Client,
+    retries: u32,
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+impl Transport {
+    pub fn new(options: &HttpOptions) -> Result<Self> {
+        let proxy = match &options.proxy {
+            Some(url) => Some(Proxy::all(url)
+                .map_err(|e| Error::InvalidInput(format!("Invalid proxy URL {}: {}", url, e)))?),
+            None => None,
+        };
+        let certificates = match &options.ca_bundle {
+            Some(path) => {
+                let pem = fs::read(path).io_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+                let certificates = Certificate::from_pem_bundle(&pem)
+                    .map_err(|e| Error::InvalidInput(format!("Invalid CA bundle {}: {}", path.display(), e)))?;
+                if certificates.is_empty() {
+                    return Err(Error::InvalidInput(format!("No certificates in CA bundle {}", path.display())));
+                }
+                certificates
+            }
+            None => Vec::new(),
+        };
+
+        let builder = || {
+            let mut builder = Client::builder()
+                .user_agent(options.user_agent.as_str())
+                .connect_timeout(options.connect_timeout);
+            if let Some(proxy) = &proxy {
+                builder = builder.proxy(proxy.clone());
+            }
+            for certificate in &certificates {
+                builder = builder.add_root_certificate(certificate.clone());
+            }
+            builder
+        };
+
+        Ok(Self {
+            client: builder().timeout(options.timeout).build()?,
+            downloads: builder().build()?,
+            retries: options.retries,
+            metrics: Arc::new(Mutex::new(Metrics::default())),
+        })
+    }
+
+    /// The process-wide transport, created with default options unless
+    /// [`configure`](Self::configure) was called first.
+    pub fn shared() -> Result<&'static Transport> {
+        if let Some(transport) = SHARED.get() {
+            return Ok(transport);
+        }
+        let transport = Transport::new(&HttpOptions::default())?;
+        Ok(SHARED.get_or_init(|| transport))
+    }
+
+    /// Creates the shared transport with `options`. Fails once it is already in use.
+    pub fn configure(options: &HttpOptions) -> Result<()> {
+        SHARED.set(Transport::new(options)?)
+            .map_err(|_| Error::InvalidInput("The shared HTTP transport is already in use".to_string()))
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Sends an API request authorized with `token`. The response is returned whatever its
+    /// status; only requests that got no response at all are retried.
+    pub async fn send(&self, method: Method, url: &str, token: &str) -> Result<Response> {
+        let token = HeaderValue::from_str(token)?;
+        self.execute(&self.client, method, url, Some(&token)).await
+    }
+
+    /// Fetches a URL without credentials, e.g. an attachment from the CDN. Only connecting is
+    /// timed out, so large files are not cut off.
+    pub async fn get(&self, url: &str) -> Result<Response> {
+        self.execute(&self.downloads, Method::GET, url, None).await
+    }
+
+    async fn execute(&self, client: &Client, method: Method, url: &str, token: Option<&HeaderValue>) -> Result<Response> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+
+        loop {
+            let mut request = client.request(method.clone(), url);
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, token.clone());
+            }
+
+            let started = Instant::now();
+            let result = request.send().await;
+            let elapsed = started.elapsed();
+
+            let error = {
+                let mut metrics = self.metrics.lock().unwrap();
+                metrics.requests += 1;
+                metrics.total_latency += elapsed;
+                match result {
+                    Ok(response) => {
+                        *metrics.responses.entry(response.status().as_u16()).or_default() += 1;
+                        debug!(%method, url, status = response.status().as_u16(), elapsed_ms = elapsed.as_millis() as u64, "HTTP request");
+                        return Ok(response);
+                    }
+                    Err(error) => {
+                        metrics.transport_errors += 1;
+                        if error.is_timeout() {
+                            metrics.timeouts += 1;
+                        }
+                        if attempt >= self.retries || !is_transient(&error, &method) {
+                            return Err(error.into());
+                        }
+                        metrics.retries += 1;
+                        error
+                    }
+                }
+            };
+
+            attempt += 1;
+            warn!(%method, url, %error, backoff_ms = backoff.as_millis() as u64, attempt, max_retries = self.retries, "Request failed, retrying");
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Errors where no response arrived: refused or dropped connections and timeouts.
+///
+/// Only `GET` and `HEAD` are sent again after the request may have reached Discord; other
+/// methods are retried only when connecting failed. A `DELETE` that timed out may still have
+/// deleted the message, and sending it again would count it as already deleted.
+fn is_transient(error: &reqwest::Error, method: &Method) -> bool {
+    if error.is_builder() {
+        return false;
+    }
+    let safe = matches!(*method, Method::GET | Method::HEAD);
+    error.is_connect() || (safe && (error.is_timeout() || error.is_request()))
+}
@@ -0,0 +1,427 @@
+use discord_message_deleter::{ratelimit::RateLimiter, Progress, RunControl};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Gauge, List, ListItem, Paragraph},
+    DefaultTerminal, Frame,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Log lines kept for the log pane.
+const LOG_CAPACITY: usize = 2000;
+
+/// Deletions older than this no longer count towards the current rate.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long the UI waits for a key press before redrawing.
+const FRAME: Duration = Duration::from_millis(100);
+
+/// Log lines captured for the dashboard's log pane instead of being written to stderr, which
+/// would tear the screen.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+/// Collects one formatted event and hands it to the buffer when dropped.
+pub struct LogWriter {
+    buffer: LogBuffer,
+    bytes: Vec<u8>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let text = String::from_utf8_lossy(&self.bytes);
+        let mut lines = self.buffer.lines.lock().unwrap();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if lines.len() == LOG_CAPACITY {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter { buffer: self.clone(), bytes: Vec::new() }
+    }
+}
+
+/// What the run tells the dashboard.
+pub enum Update {
+    ChannelStarted { index: usize, label: String },
+    Progress(Progress),
+    ChannelFinished { index: usize, label: String, error: Option<String> },
+}
+
+/// Live view of a `delete` run, drawn on its own thread.
+///
+/// Keys: `p`/space pause or resume, `r` resume, `s` skip the current channel, `q`/Esc stop after
+/// the message being deleted, arrows and page keys scroll the log.
+pub struct Dashboard {
+    updates: Sender<Update>,
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Dashboard {
+    pub fn start(control: RunControl, log: LogBuffer, channels: usize, dry_run: bool) -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        let (updates, receiver) = mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let state = State::new(control, log, channels, dry_run);
+        let thread_done = Arc::clone(&done);
+        let thread = thread::spawn(move || {
+            let result = run(terminal, state, receiver, &thread_done);
+            ratatui::restore();
+            result
+        });
+
+        Ok(Self { updates, done, thread: Some(thread) })
+    }
+
+    pub fn sender(&self) -> Sender<Update> {
+        self.updates.clone()
+    }
+
+    /// Closes the dashboard and gives the terminal back.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.done.store(true, Ordering::SeqCst);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|_| Err(io::Error::other("dashboard thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Restores the terminal when the run ends with an error before [`Dashboard::finish`].
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+struct State {
+    control: RunControl,
+    log: LogBuffer,
+    /// Lines scrolled up from the newest log line.
+    scroll: usize,
+    dry_run: bool,
+    started: Instant,
+    channels: usize,
+    channels_done: usize,
+    active: Vec<(usize, String)>,
+    /// Messages the listings announced up front, when they know.
+    expected: usize,
+    found: usize,
+    deleted: usize,
+    failed: usize,
+    skipped: usize,
+    system: usize,
+    matched: usize,
+    archived: usize,
+    retry_round: Option<(u32, usize)>,
+    recent: VecDeque<Instant>,
+}
+
+impl State {
+    fn new(control: RunControl, log: LogBuffer, channels: usize, dry_run: bool) -> Self {
+        Self {
+            control,
+            log,
+            scroll: 0,
+            dry_run,
+            started: Instant::now(),
+            channels,
+            channels_done: 0,
+            active: Vec::new(),
+            expected: 0,
+            found: 0,
+            deleted: 0,
+            failed: 0,
+            skipped: 0,
+            system: 0,
+            matched: 0,
+            archived: 0,
+            retry_round: None,
+            recent: VecDeque::new(),
+        }
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::ChannelStarted { index, label } => self.active.push((index, label)),
+            Update::ChannelFinished { index, label, error } => {
+                self.active.retain(|(active, _)| *active != index);
+                self.channels_done += 1;
+                self.retry_round = None;
+                if let Some(error) = error {
+                    self.log(format!("{}: {}", label, error));
+                }
+            }
+            Update::Progress(event) => self.progress(event),
+        }
+    }
+
+    fn progress(&mut self, event: Progress) {
+        match event {
+            Progress::BatchChecked { found, .. } => self.found += found,
+            Progress::Expected { total } => self.expected += total,
+            Progress::WouldDelete(_) => self.handled(|s| s.matched += 1),
+            Progress::Archived { .. } => self.archived += 1,
+            Progress::Deleted { .. } => self.handled(|s| s.deleted += 1),
+            Progress::Failed { .. } => self.handled(|s| s.failed += 1),
+            Progress::Skipped { .. } => self.handled(|s| s.skipped += 1),
+            Progress::Undeletable { .. } => self.handled(|s| s.system += 1),
+            Progress::RetryRound { round, pending } => self.retry_round = Some((round, pending)),
+            _ => {}
+        }
+    }
+
+    fn handled(&mut self, count: impl FnOnce(&mut Self)) {
+        count(self);
+        let now = Instant::now();
+        self.recent.push_back(now);
+        while self.recent.front().is_some_and(|t| now.duration_since(*t) > RATE_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+
+    fn log(&self, line: String) {
+        let mut lines = self.log.lines.lock().unwrap();
+        if lines.len() == LOG_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    fn processed(&self) -> usize {
+        self.deleted + self.failed + self.skipped + self.system + self.matched
+    }
+
+    /// Messages handled per minute, over the last minute.
+    fn rate(&self) -> f64 {
+        let window = self.started.elapsed().min(RATE_WINDOW).as_secs_f64();
+        if window < 1.0 {
+            return 0.0;
+        }
+        let recent = self.recent.iter().filter(|t| t.elapsed() <= RATE_WINDOW).count();
+        recent as f64 * 60.0 / window
+    }
+
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match code {
+            KeyCode::Char('p') | KeyCode::Char(' ') if self.control.is_paused() => self.control.resume(),
+            KeyCode::Char('p') | KeyCode::Char(' ') => self.control.pause(),
+            KeyCode::Char('r') => self.control.resume(),
+            KeyCode::Char('s') => {
+                self.control.skip_current();
+                self.log("Skipping the current channel".to_string());
+            }
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => self.stop(),
+            KeyCode::Char('q') | KeyCode::Esc => self.stop(),
+            KeyCode::Up => self.scroll += 1,
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll += 10,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        if !self.control.is_stopped() {
+            self.control.stop();
+            self.control.resume();
+            self.log("Stopping after the current message...".to_string());
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [header, gauge, stats, log, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.render_header(frame, header);
+        self.render_gauge(frame, gauge);
+        self.render_stats(frame, stats);
+        self.render_log(frame, log);
+        frame.render_widget(
+            Line::from(" p pause/resume   s skip channel   q quit   ↑↓ PgUp PgDn scroll log").dark_gray(),
+            help,
+        );
+    }
+
+    fn render_header(&self, frame: &mut Frame, area: Rect) {
+        let status = if self.control.is_stopped() {
+            Span::styled(" STOPPING ", Style::new().fg(Color::Black).bg(Color::Red))
+        } else if self.control.is_paused() {
+            Span::styled(" PAUSED ", Style::new().fg(Color::Black).bg(Color::Yellow))
+        } else {
+            Span::styled(" RUNNING ", Style::new().fg(Color::Black).bg(Color::Green))
+        };
+
+        let channels = match self.active.as_slice() {
+            [] => "waiting".to_string(),
+            active => active.iter()
+                .map(|(index, label)| format!("{}/{} {}", index + 1, self.channels, label))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        let mut line = vec![status, Span::raw(format!(" Channel {}", channels))];
+        if let Some((round, pending)) = self.retry_round {
+            line.push(format!("  retry round {} ({} messages)", round, pending).yellow());
+        }
+
+        let title = if self.dry_run { " Discord Message Deleter (dry run) " } else { " Discord Message Deleter " };
+        frame.render_widget(Paragraph::new(Line::from(line)).block(Block::bordered().title(title)), area);
+    }
+
+    fn render_gauge(&self, frame: &mut Frame, area: Rect) {
+        let processed = self.processed();
+        let (total, suffix) = if self.expected >= self.found && self.expected > 0 {
+            (self.expected, "")
+        } else {
+            (self.found, " found so far")
+        };
+        let ratio = if total == 0 { 0.0 } else { (processed as f64 / total as f64).min(1.0) };
+
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(format!(" Channels {}/{} ", self.channels_done, self.channels)))
+            .gauge_style(Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .ratio(ratio)
+            .label(format!("{} / {}{}", processed, total, suffix));
+        frame.render_widget(gauge, area);
+    }
+
+    fn render_stats(&self, frame: &mut Frame, area: Rect) {
+        let rate = self.rate();
+        let remaining = self.expected.max(self.found).saturating_sub(self.processed());
+        let eta = if rate > 0.0 && remaining > 0 {
+            format_duration(Duration::from_secs_f64(remaining as f64 / rate * 60.0))
+        } else {
+            "-".to_string()
+        };
+
+        let counts = if self.dry_run {
+            Line::from(vec![" Would delete ".into(), self.matched.to_string().green().bold()])
+        } else {
+            Line::from(vec![
+                " Deleted ".into(), self.deleted.to_string().green().bold(),
+                "   Failed ".into(), self.failed.to_string().red().bold(),
+                "   Skipped ".into(), self.skipped.to_string().yellow().bold(),
+                "   System ".into(), self.system.to_string().dark_gray().bold(),
+                "   Archived ".into(), self.archived.to_string().bold(),
+            ])
+        };
+
+        let limits = RateLimiter::shared().status();
+        let waiting = match limits.waiting_until {
+            Some(until) => format!(", waiting {:.1}s now", until.saturating_duration_since(Instant::now()).as_secs_f64()),
+            None => String::new(),
+        };
+        let pace = Line::from(format!(
+            " Rate {:.0}/min   ETA {}   Elapsed {}   Rate limits: {} hit, {} waits ({:.0}s){}",
+            rate,
+            eta,
+            format_duration(self.started.elapsed()),
+            limits.rate_limited,
+            limits.waits,
+            limits.total_wait.as_secs_f64(),
+            waiting,
+        ));
+
+        frame.render_widget(Paragraph::new(vec![counts, pace]).block(Block::bordered()), area);
+    }
+
+    fn render_log(&self, frame: &mut Frame, area: Rect) {
+        let lines = self.log.lines.lock().unwrap();
+        let height = usize::from(area.height.saturating_sub(2));
+        let scroll = self.scroll.min(lines.len().saturating_sub(height));
+        let end = lines.len() - scroll;
+        let start = end.saturating_sub(height);
+
+        let items: Vec<_> = lines.range(start..end)
+            .map(|line| {
+                let style = if line.contains("ERROR") {
+                    Style::new().red()
+                } else if line.contains("WARN") {
+                    Style::new().yellow()
+                } else {
+                    Style::new()
+                };
+                ListItem::new(line.as_str()).style(style)
+            })
+            .collect();
+
+        let title = if scroll > 0 { format!(" Log (scrolled up {}) ", scroll) } else { " Log ".to_string() };
+        frame.render_widget(List::new(items).block(Block::bordered().title(title)), area);
+    }
+}
+
+fn run(mut terminal: DefaultTerminal, mut state: State, updates: Receiver<Update>, done: &AtomicBool) -> io::Result<()> {
+    loop {
+        while let Ok(update) = updates.try_recv() {
+            state.apply(update);
+        }
+        terminal.draw(|frame| state.render(frame))?;
+
+        if done.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        if event::poll(FRAME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    state.key(key.code, key.modifiers);
+                }
+            }
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
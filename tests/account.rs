@@ -0,0 +1,65 @@
+//! Looking up the account a token belongs to and the channels it can see through the mock server.
+
+mod mock;
+
+use discord_message_deleter::{
+    guild::list_guild_channels,
+    transport::{HttpOptions, Transport},
+    DiscordClient, Error,
+};
+use mock::{MockDiscord, AUTHOR, TOKEN};
+
+#[tokio::test]
+async fn validate_token_returns_the_token_owner() {
+    let mock = MockDiscord::start().await;
+
+    let user = DiscordClient::validate_token(Transport::shared().unwrap(), &mock.api_base(), TOKEN).await.unwrap();
+
+    assert_eq!(user.id, AUTHOR);
+    assert_eq!(user.username, "mockuser");
+    assert_eq!(user.display_name(), "Mock User");
+    assert_eq!(user.flags, 64);
+}
+
+#[tokio::test]
+async fn validate_token_uses_the_given_transport() {
+    let mock = MockDiscord::start().await;
+    let transport = Transport::new(&HttpOptions::default()).unwrap();
+
+    DiscordClient::validate_token(&transport, &mock.api_base(), TOKEN).await.unwrap();
+
+    assert_eq!(transport.metrics().responses.get(&200), Some(&1));
+}
+
+#[tokio::test]
+async fn validate_token_rejects_unknown_tokens() {
+    let mock = MockDiscord::start().await;
+
+    let result = DiscordClient::validate_token(Transport::shared().unwrap(), &mock.api_base(), "not-a-token").await;
+
+    assert!(matches!(result, Err(Error::InvalidToken)));
+}
+
+#[tokio::test]
+async fn validate_channel_reports_unknown_channels_as_inaccessible() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+
+    assert!(DiscordClient::validate_channel(Transport::shared().unwrap(), &mock.api_base(), TOKEN, &channel_id).await.unwrap());
+    assert!(!DiscordClient::validate_channel(Transport::shared().unwrap(), &mock.api_base(), TOKEN, "123456789012345678").await.unwrap());
+    let rejected = DiscordClient::validate_channel(Transport::shared().unwrap(), &mock.api_base(), "not-a-token", &channel_id).await;
+    assert!(matches!(rejected, Err(Error::InvalidToken)));
+}
+
+#[tokio::test]
+async fn guild_channels_are_listed_when_threads_cannot_be_read() {
+    let mock = MockDiscord::start().await;
+    let guild_id = "444444444444444444";
+    let channel_id = mock.add_guild_channel(guild_id);
+
+    // The mock serves no thread lists, so both thread requests fail.
+    let channels = list_guild_channels(Transport::shared().unwrap(), TOKEN, &mock.api_base(), guild_id).await.unwrap();
+
+    assert_eq!(channels.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), vec![channel_id.as_str()]);
+    assert_eq!(mock.count_requests(hyper::Method::GET, "/threads/active"), 1);
+}
@@ -0,0 +1,91 @@
+//! Downloading message attachments from the mock server's CDN.
+
+mod mock;
+
+use discord_message_deleter::{
+    attachments::{AttachmentDownloader, AttachmentOptions, DownloadStatus},
+    transport::Transport,
+    Error, Message,
+};
+use mock::{MockDiscord, AUTHOR};
+use serde_json::json;
+use std::fs;
+
+/// A message of [`AUTHOR`] with one attachment of the announced `size`.
+fn message_with(url: &str, size: Option<u64>) -> Message {
+    serde_json::from_value(json!({
+        "id": "700000000000000001",
+        "author": { "id": AUTHOR },
+        "timestamp": "2024-01-01T00:00:00Z",
+        "attachments": [{ "id": "800000000000000001", "filename": "notes.txt", "url": url, "size": size }],
+    }))
+    .unwrap()
+}
+
+fn open(dir: &std::path::Path, max_size: u64) -> AttachmentDownloader {
+    let options = AttachmentOptions { max_size, concurrency: 1 };
+    AttachmentDownloader::open(dir, options, Transport::shared().unwrap().clone()).unwrap()
+}
+
+#[tokio::test]
+async fn downloads_are_hashed_and_kept() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let url = mock.add_attachment(&channel_id, "notes.txt", b"remember the milk");
+    let archive = tempfile::tempdir().unwrap();
+
+    let entries = open(archive.path(), 1024).download(&message_with(&url, Some(17))).await.unwrap();
+
+    assert_eq!(entries[0].status, DownloadStatus::Downloaded);
+    assert_eq!(
+        entries[0].sha256.as_deref(),
+        Some("0057061a4f16934b96f73f579167f795c4d4c20d8c501fc495197c550af51110"),
+    );
+    let saved = fs::read(archive.path().join(entries[0].path.as_ref().unwrap())).unwrap();
+    assert_eq!(saved, b"remember the milk");
+}
+
+#[tokio::test]
+async fn a_download_of_the_wrong_size_is_discarded() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let url = mock.add_attachment(&channel_id, "notes.txt", b"remember the milk");
+    let archive = tempfile::tempdir().unwrap();
+
+    let result = open(archive.path(), 1024).download(&message_with(&url, Some(100))).await;
+
+    assert!(matches!(result, Err(Error::Download(_))), "{:?}", result.err());
+    let files: Vec<_> = fs::read_dir(archive.path().join("attachments")).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(files, vec!["manifest.jsonl"]);
+}
+
+#[tokio::test]
+async fn a_download_of_unknown_size_stops_at_the_limit() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let url = mock.add_streamed_attachment(&channel_id, "notes.txt", &[b'x'; 64 * 1024]);
+    let archive = tempfile::tempdir().unwrap();
+
+    let entries = open(archive.path(), 4096).download(&message_with(&url, None)).await.unwrap();
+
+    assert_eq!(entries[0].status, DownloadStatus::SkippedTooLarge);
+    assert_eq!(entries[0].size, None);
+    let files: Vec<_> = fs::read_dir(archive.path().join("attachments")).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(files, vec!["manifest.jsonl"]);
+}
+
+#[tokio::test]
+async fn a_download_of_unknown_size_under_the_limit_records_its_size() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let url = mock.add_streamed_attachment(&channel_id, "notes.txt", &[b'x'; 3000]);
+    let archive = tempfile::tempdir().unwrap();
+
+    let entries = open(archive.path(), 4096).download(&message_with(&url, None)).await.unwrap();
+
+    assert_eq!((entries[0].status, entries[0].size), (DownloadStatus::Downloaded, Some(3000)));
+}
@@ -0,0 +1,282 @@
+//! Deleting messages against the mock server: the full pipeline, failures that are skipped or
+//! retried, and rate limits.
+
+mod mock;
+
+use chrono::Utc;
+use discord_message_deleter::{
+    checkpoint::Checkpoint,
+    ratelimit::{self, RateLimiter},
+    retry::FailedDeletion,
+    transport::Transport,
+    DiscordClient, Error, Message, RunOptions,
+};
+use hyper::Method;
+use mock::{MockDiscord, AUTHOR, OTHER, TOKEN};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+fn options() -> RunOptions {
+    RunOptions { retry_rounds: 0, ..RunOptions::default() }
+}
+
+/// Fetches the messages with the given IDs from the channel's first history page.
+async fn fetch(client: &DiscordClient, ids: &[&str]) -> Vec<Message> {
+    let page = client.get_messages(None).await.expect("fetch messages");
+    let messages: Vec<Message> = page.into_iter().filter(|m| ids.contains(&m.id.as_str())).collect();
+    assert_eq!(messages.len(), ids.len());
+    messages
+}
+
+/// Hands `messages` straight to `process_messages`, without listing the channel.
+async fn process(client: &DiscordClient, messages: Vec<Message>) {
+    let (tx, rx) = mpsc::channel(messages.len().max(1));
+    for message in messages {
+        tx.send(message).await.unwrap();
+    }
+    drop(tx);
+    client.process_messages(rx, None).await.expect("process messages");
+}
+
+#[tokio::test]
+async fn delete_all_messages_removes_only_the_author_messages() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_conversation(&channel_id, 150, 3);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!(stats.deleted, own.len());
+    assert_eq!(stats.failed, 0);
+    assert_eq!(mock.remaining(&channel_id).len(), 150 - own.len());
+    assert_eq!(mock.count_requests(Method::DELETE, "/messages/"), own.len());
+}
+
+#[tokio::test]
+async fn process_messages_skips_unknown_and_system_messages() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let kept = mock.add_message(&channel_id, AUTHOR, "hello");
+    let gone = mock.add_message(&channel_id, AUTHOR, "deleted elsewhere");
+    let call = mock.add_message_of_type(&channel_id, AUTHOR, "", 3);
+    let pin = mock.add_message_of_type(&channel_id, AUTHOR, "", 6);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let messages = fetch(&client, &[&kept, &gone, &call, &pin]).await;
+    mock.fail_delete(&gone, 404, 1);
+    process(&client, messages).await;
+
+    let stats = client.stats();
+    // Pin notices are system messages too, but their author may delete them.
+    assert_eq!((stats.deleted, stats.skipped, stats.system, stats.failed), (2, 1, 1, 0));
+    assert_eq!(mock.remaining(&channel_id), vec![gone, call.clone()]);
+    // Undeletable system messages are never sent to Discord.
+    assert_eq!(mock.count_requests(Method::DELETE, &call), 0);
+}
+
+#[tokio::test]
+async fn server_errors_are_retried_with_backoff() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let message_id = mock.add_message(&channel_id, AUTHOR, "flaky");
+    mock.fail_delete(&message_id, 502, 1);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!((stats.deleted, stats.failed), (1, 0));
+    assert_eq!(mock.count_requests(Method::DELETE, &message_id), 2);
+}
+
+#[tokio::test]
+async fn rate_limited_requests_wait_for_retry_after() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 5);
+    mock.rate_limit_next_delete(0.3);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!(stats.deleted, own.len());
+    assert_eq!(mock.rate_limited(), 1);
+    assert_eq!(mock.count_requests(Method::DELETE, "/messages/"), own.len() + 1);
+}
+
+#[tokio::test]
+async fn repeated_rate_limits_are_handed_back_to_the_caller() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let message_id = mock.add_message(&channel_id, AUTHOR, "stuck");
+    for _ in 0..10 {
+        mock.rate_limit_next_delete(0.01);
+    }
+
+    let url = format!("{}/channels/{}/messages/{}", mock.api_base(), channel_id, message_id);
+    let result = ratelimit::send(Transport::shared().unwrap(), TOKEN, &RateLimiter::new(), None, Method::DELETE, &url).await;
+
+    assert!(matches!(result, Err(Error::RateLimited { .. })), "{:?}", result.err());
+    assert_eq!(mock.count_requests(Method::DELETE, &message_id), 3);
+}
+
+#[tokio::test]
+async fn a_client_waits_in_its_own_rate_limiter() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    mock.add_messages(&channel_id, 2);
+    mock.rate_limit_next_delete(0.01);
+    let limiter = Arc::new(RateLimiter::new());
+
+    let client = mock.client(&channel_id).rate_limiter(Arc::clone(&limiter)).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!(stats.deleted, 2);
+    assert_eq!(limiter.status().rate_limited, 1);
+}
+
+#[tokio::test]
+async fn rate_limit_headers_are_respected_before_sending() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 12);
+    mock.limit_deletes(3, Duration::from_millis(400));
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!(stats.deleted, own.len());
+    assert_eq!(mock.rate_limited(), 0);
+}
+
+#[tokio::test]
+async fn persistent_failures_are_given_up_and_kept_for_retry() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 4);
+    mock.always_fail_delete(&own[1], 403);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!((stats.deleted, stats.failed), (3, 1));
+    let failed = client.failed_deletions();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].message_id, own[1]);
+    assert_eq!(failed[0].status, Some(403));
+    assert_eq!(mock.remaining(&channel_id), vec![own[1].clone()]);
+}
+
+#[tokio::test]
+async fn retry_rounds_delete_messages_that_failed_earlier() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 3);
+    mock.fail_delete(&own[0], 403, 1);
+
+    let options = RunOptions { retry_rounds: 1, ..RunOptions::default() };
+    let client = mock.client(&channel_id).options(options).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!((stats.deleted, stats.failed), (3, 0));
+    assert!(client.failed_deletions().is_empty());
+    assert!(mock.remaining(&channel_id).is_empty());
+}
+
+#[tokio::test]
+async fn other_users_messages_are_never_requested() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let theirs = mock.add_message(&channel_id, OTHER, "not mine");
+    mock.add_message(&channel_id, AUTHOR, "mine");
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!(stats.deleted, 1);
+    assert_eq!(mock.count_requests(Method::DELETE, &theirs), 0);
+    assert_eq!(mock.remaining(&channel_id), vec![theirs]);
+}
+
+#[tokio::test]
+async fn failures_deferred_by_process_messages_are_given_up_on_request() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 2);
+    mock.always_fail_delete(&own[0], 403);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let messages = fetch(&client, &[&own[0], &own[1]]).await;
+    process(&client, messages).await;
+    assert_eq!((client.stats().deleted, client.stats().failed), (1, 0));
+
+    client.retry_deferred().await.unwrap();
+
+    assert_eq!(client.stats().failed, 1);
+    let failed = client.failed_deletions();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].message_id, own[0]);
+}
+
+#[tokio::test]
+async fn a_fatal_error_keeps_the_rest_of_the_retry_queue() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 4);
+    mock.always_fail_delete(&own[1], 403);
+    mock.always_fail_delete(&own[2], 401);
+
+    let client = mock.client(&channel_id).options(options()).build().unwrap();
+    let messages = fetch(&client, &own.iter().map(String::as_str).collect::<Vec<_>>()).await;
+    let queued: Vec<FailedDeletion> = messages.iter().rev()
+        .map(|message| FailedDeletion {
+            channel_id: channel_id.clone(),
+            author_id: AUTHOR.to_string(),
+            message_id: message.id.clone(),
+            timestamp: Some(message.timestamp),
+            reason: "earlier failure".to_string(),
+            status: Some(500),
+            attempts: 1,
+            failed_at: Utc::now(),
+        })
+        .collect();
+
+    let result = client.retry_deletions(queued).await;
+
+    assert!(matches!(result, Err(Error::InvalidToken)), "{:?}", result.err());
+    let mut kept: Vec<String> = client.failed_deletions().into_iter().map(|d| d.message_id).collect();
+    kept.sort();
+    assert_eq!(kept, own[1..].to_vec());
+    assert_eq!(mock.remaining(&channel_id), own[1..].to_vec());
+}
+
+#[tokio::test]
+async fn resuming_with_other_date_options_is_refused() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 3);
+    let checkpoints = tempfile::tempdir().unwrap();
+    let path = Checkpoint::path(checkpoints.path(), &channel_id, AUTHOR);
+    let checkpointed = |before: Option<chrono::DateTime<Utc>>| RunOptions {
+        checkpoint_dir: Some(checkpoints.path().to_path_buf()),
+        resume: true,
+        before,
+        ..options()
+    };
+    let before = Some(Utc::now() + chrono::Duration::days(1));
+
+    mock.client(&channel_id).options(checkpointed(before)).build().unwrap()
+        .delete_all_messages().await.unwrap();
+    // Pretend the run was interrupted, then resume it without the date range.
+    let mut checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+    checkpoint.completed = false;
+    checkpoint.save(&path).unwrap();
+    let result = mock.client(&channel_id).options(checkpointed(None)).build().unwrap()
+        .delete_all_messages().await;
+
+    assert!(matches!(result, Err(Error::InvalidInput(_))), "{:?}", result.err());
+    // The same options still resume.
+    mock.client(&channel_id).options(checkpointed(before)).build().unwrap()
+        .delete_all_messages().await.unwrap();
+    assert_eq!(mock.count_requests(Method::DELETE, "/messages/"), own.len());
+}
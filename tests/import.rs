@@ -0,0 +1,72 @@
+//! Deleting the messages of a Discord data package against the mock server.
+
+mod mock;
+
+use discord_message_deleter::{
+    attachments::AttachmentOptions,
+    import,
+    transport::{HttpOptions, Transport},
+    RunOptions,
+};
+use mock::{MockDiscord, AUTHOR};
+use serde_json::json;
+use std::{fs, path::Path};
+
+/// Writes a data package in the `messages/c<channel>/messages.json` layout.
+fn write_package(root: &Path, channel_id: &str, messages: serde_json::Value) {
+    let dir = root.join("messages").join(format!("c{}", channel_id));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(root.join("messages/index.json"), json!({ channel_id: "Direct Message with mock" }).to_string()).unwrap();
+    fs::write(dir.join("channel.json"), json!({ "id": channel_id }).to_string()).unwrap();
+    fs::write(dir.join("messages.json"), messages.to_string()).unwrap();
+}
+
+#[tokio::test]
+async fn package_messages_with_attachments_are_archived_and_deleted() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let with_file = mock.add_message(&channel_id, AUTHOR, "holiday photos");
+    let with_big_file = mock.add_message(&channel_id, AUTHOR, "a video");
+    let photo = mock.add_attachment(&channel_id, "photo.png", b"not really a png");
+    let video = mock.add_attachment(&channel_id, "video.mp4", &[0; 4096]);
+
+    let package = tempfile::tempdir().unwrap();
+    write_package(package.path(), &channel_id, json!([
+        { "ID": with_file, "Contents": "holiday photos", "Attachments": photo },
+        { "ID": with_big_file, "Contents": "a video", "Attachments": video },
+    ]));
+    let channels = import::read_data_package(package.path()).unwrap();
+    assert_eq!(channels.len(), 1);
+
+    let archive = tempfile::tempdir().unwrap();
+    let options = RunOptions {
+        archive_dir: Some(archive.path().to_path_buf()),
+        attachments: Some(AttachmentOptions { max_size: 1024, concurrency: 2 }),
+        retry_rounds: 0,
+        ..RunOptions::default()
+    };
+    let transport = Transport::new(&HttpOptions::default()).unwrap();
+    let client = mock.client(&channel_id)
+        .transport(transport.clone())
+        .options(options)
+        .known_messages(channels[0].to_messages(AUTHOR))
+        .build()
+        .unwrap();
+    let stats = client.delete_all_messages().await.unwrap();
+
+    assert_eq!((stats.deleted, stats.archived, stats.skipped, stats.failed), (2, 2, 0, 0));
+    assert!(mock.remaining(&channel_id).is_empty());
+    // Both downloads and both deletions went through the client's transport.
+    assert_eq!(transport.metrics().responses.get(&200), Some(&2));
+    assert_eq!(transport.metrics().responses.get(&204), Some(&2));
+
+    let attachments = archive.path().join(&channel_id).join("attachments");
+    let saved = fs::read(attachments.join(format!("{}_{}_photo.png", with_file, photo.split('/').nth_back(1).unwrap()))).unwrap();
+    assert_eq!(saved, b"not really a png");
+    let manifest = fs::read_to_string(attachments.join("manifest.jsonl")).unwrap();
+    let entries: Vec<serde_json::Value> = manifest.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let status = |filename: &str| entries.iter().find(|e| e["filename"] == filename).map(|e| (e["status"].clone(), e["size"].clone()));
+    assert_eq!(status("photo.png"), Some((json!("downloaded"), json!(16))));
+    // The package does not know sizes, so the limit applies to the downloaded bytes.
+    assert_eq!(status("video.mp4"), Some((json!("skipped_too_large"), json!(4096))));
+}
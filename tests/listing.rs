@@ -0,0 +1,160 @@
+//! Listing messages through the history and search endpoints of the mock server, in dry and
+//! export-only runs so nothing is deleted.
+
+mod mock;
+
+use chrono::{TimeZone, Utc};
+use discord_message_deleter::{search::ListingStrategy, ClientBuilder, DiscordClient, Error, Progress, RunOptions};
+use hyper::Method;
+use mock::{MockDiscord, OTHER};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+fn dry_run(strategy: ListingStrategy) -> RunOptions {
+    RunOptions { dry_run: true, strategy, ..RunOptions::default() }
+}
+
+/// Runs `list_messages` and `process_messages` side by side, like `delete_all_messages` does.
+async fn list(client: &DiscordClient) {
+    let (tx, rx) = mpsc::channel(100);
+    let lister = client.clone();
+    let listing = tokio::spawn(async move { lister.list_messages(tx).await });
+    client.process_messages(rx, None).await.expect("process messages");
+    listing.await.unwrap().expect("list messages");
+}
+
+/// Builds the client and collects the IDs it reports as would-be deletions, in order.
+fn recording(builder: ClientBuilder) -> (DiscordClient, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    let client = builder
+        .on_progress(move |event| {
+            if let Progress::WouldDelete(message) = event {
+                sink.lock().unwrap().push(message.id.clone());
+            }
+        })
+        .build()
+        .unwrap();
+    (client, seen)
+}
+
+fn newest_first(mut ids: Vec<String>) -> Vec<String> {
+    ids.reverse();
+    ids
+}
+
+#[tokio::test]
+async fn history_pages_through_the_channel_and_keeps_only_the_author() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_conversation(&channel_id, 250, 3);
+
+    let (client, seen) = recording(mock.client(&channel_id).options(dry_run(ListingStrategy::History)));
+    list(&client).await;
+
+    assert_eq!(*seen.lock().unwrap(), newest_first(own));
+    assert_eq!(client.stats().matched, seen.lock().unwrap().len());
+    assert!(mock.count_requests(Method::GET, "/messages") >= 3);
+    assert_eq!(mock.count_requests(Method::DELETE, "/messages/"), 0);
+    assert_eq!(mock.remaining(&channel_id).len(), 250);
+}
+
+#[tokio::test]
+async fn history_respects_the_date_range() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    // Messages are posted one minute apart from 2024-01-01 00:00 UTC.
+    let own = mock.add_messages(&channel_id, 30);
+
+    let options = RunOptions {
+        after: Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 10, 0).unwrap()),
+        before: Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 20, 0).unwrap()),
+        ..dry_run(ListingStrategy::History)
+    };
+    let (client, seen) = recording(mock.client(&channel_id).options(options));
+    list(&client).await;
+
+    assert_eq!(*seen.lock().unwrap(), newest_first(own[10..20].to_vec()));
+}
+
+#[tokio::test]
+async fn search_waits_for_the_index_and_pages_through_results() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_conversation(&channel_id, 90, 4);
+    mock.index_not_ready(2);
+
+    let (client, seen) = recording(mock.client(&channel_id).options(dry_run(ListingStrategy::Search)));
+    list(&client).await;
+
+    assert_eq!(*seen.lock().unwrap(), newest_first(own.clone()));
+    // Two "not ready" answers, then one request per page of 25.
+    assert_eq!(mock.count_requests(Method::GET, "/messages/search"), 2 + own.len().div_ceil(25));
+}
+
+#[tokio::test]
+async fn search_uses_the_guild_endpoint_for_server_channels() {
+    let mock = MockDiscord::start().await;
+    let guild_id = "333333333333333333";
+    let channel_id = mock.add_guild_channel(guild_id);
+    let own = mock.add_conversation(&channel_id, 10, 2);
+
+    let (client, seen) = recording(mock.client(&channel_id).guild(guild_id).options(dry_run(ListingStrategy::Search)));
+    list(&client).await;
+
+    assert_eq!(*seen.lock().unwrap(), newest_first(own));
+    assert!(mock.count_requests(Method::GET, &format!("/guilds/{}/messages/search", guild_id)) > 0);
+    assert_eq!(mock.count_requests(Method::GET, &format!("/channels/{}/messages/search", channel_id)), 0);
+}
+
+#[tokio::test]
+async fn messages_stream_yields_the_author_messages() {
+    use futures::StreamExt;
+
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_conversation(&channel_id, 40, 2);
+    mock.add_message(&channel_id, OTHER, "last word");
+
+    let client = mock.client(&channel_id).options(dry_run(ListingStrategy::History)).build().unwrap();
+    let ids: Vec<String> = client.messages()
+        .map(|message| message.expect("listed message").id)
+        .collect()
+        .await;
+
+    assert_eq!(ids, newest_first(own));
+}
+
+#[tokio::test]
+async fn malformed_responses_are_json_errors_and_not_retried() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    mock.add_messages(&channel_id, 3);
+    mock.malformed_history(1);
+
+    let client = mock.client(&channel_id).build().unwrap();
+    let result = client.get_messages(None).await;
+
+    assert!(matches!(result, Err(Error::Json(_))), "{:?}", result.err());
+    assert_eq!(mock.count_requests(Method::GET, "/messages"), 1);
+}
+
+#[tokio::test]
+async fn export_only_search_pages_through_every_result_once() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_conversation(&channel_id, 120, 2);
+    let archive = tempfile::tempdir().unwrap();
+
+    let options = RunOptions {
+        strategy: ListingStrategy::Search,
+        archive_dir: Some(archive.path().to_path_buf()),
+        export_only: true,
+        ..RunOptions::default()
+    };
+    let stats = mock.client(&channel_id).options(options).build().unwrap().delete_all_messages().await.unwrap();
+
+    assert_eq!((stats.archived, stats.deleted), (own.len(), 0));
+    // Nothing is deleted, so each page of 25 is requested once.
+    assert_eq!(mock.count_requests(Method::GET, "/messages/search"), own.len().div_ceil(25));
+}
@@ -0,0 +1,511 @@
+//! An in-process stand-in for the parts of the Discord API the deleter talks to.
+//!
+//! Every test starts its own [`MockDiscord`] on a free local port and points a client at it
+//! with [`ClientBuilder::api_base`]. Channels and messages live in memory; deletions, scripted
+//! failures, rate limits and an unready search index can be set up per test.
+
+#![allow(dead_code)]
+
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use discord_message_deleter::{snowflake::snowflake_from_timestamp, ClientBuilder, DiscordClient};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    net::{SocketAddr, TcpListener},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+pub const TOKEN: &str = "mock-token";
+pub const AUTHOR: &str = "111111111111111111";
+pub const OTHER: &str = "222222222222222222";
+
+/// Search results per page, as on Discord.
+const SEARCH_PAGE: usize = 25;
+
+/// Channel IDs are unique across tests, so the rate limiter shared by the whole test binary
+/// keeps their buckets apart.
+static NEXT_CHANNEL: AtomicU64 = AtomicU64::new(900_000_000_000_000_000);
+
+#[derive(Debug, Clone)]
+struct MockMessage {
+    id: u64,
+    author_id: String,
+    content: String,
+    kind: u8,
+}
+
+#[derive(Debug)]
+struct Channel {
+    guild_id: Option<String>,
+    /// Messages by ID, so the newest is last.
+    messages: BTreeMap<u64, MockMessage>,
+}
+
+/// A scripted answer to DELETE requests for one message.
+#[derive(Debug, Clone, Copy)]
+struct DeleteFailure {
+    status: u16,
+    /// Requests left to fail; `None` fails forever.
+    remaining: Option<usize>,
+}
+
+/// A Discord rate-limit bucket: `limit` requests per `window`, announced through the
+/// `x-ratelimit-*` headers.
+#[derive(Debug)]
+struct Bucket {
+    limit: u32,
+    window: Duration,
+    used: u32,
+    window_start: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    channels: HashMap<String, Channel>,
+    next_message_time: Option<DateTime<Utc>>,
+    delete_failures: HashMap<u64, DeleteFailure>,
+    /// Upcoming DELETE requests answered with a 429 carrying this `retry_after`.
+    forced_rate_limits: Vec<f64>,
+    delete_bucket: Option<Bucket>,
+    /// Search requests answered with "index not ready" before results are served.
+    index_not_ready: usize,
+    /// History requests answered with a body that is not JSON.
+    malformed_history: usize,
+    /// Files served without credentials under `/attachments/`, like the CDN.
+    attachments: HashMap<String, Vec<u8>>,
+    /// Attachment paths sent in chunks without a `Content-Length`.
+    streamed: HashSet<String>,
+    requests: Vec<(Method, String)>,
+    rate_limited: usize,
+}
+
+/// A mock Discord API server running on a background task until dropped.
+pub struct MockDiscord {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockDiscord {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let service_state = Arc::clone(&state);
+        let make_service = make_service_fn(move |_| {
+            let state = Arc::clone(&service_state);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = Arc::clone(&state);
+                    async move { Ok::<_, Infallible>(handle(&state, request).await) }
+                }))
+            }
+        });
+
+        let (shutdown, stopped) = oneshot::channel();
+        let server = Server::from_tcp(listener)
+            .expect("start mock server")
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+        tokio::spawn(server);
+
+        Self { addr, state, shutdown: Some(shutdown) }
+    }
+
+    pub fn api_base(&self) -> String {
+        format!("http://{}/api/v10", self.addr)
+    }
+
+    /// A client builder for `channel_id` and [`AUTHOR`], talking to this server.
+    pub fn client(&self, channel_id: &str) -> ClientBuilder {
+        DiscordClient::builder(TOKEN)
+            .api_base(self.api_base())
+            .channel(channel_id)
+            .author(AUTHOR)
+    }
+
+    /// Adds an empty DM channel and returns its ID.
+    pub fn add_channel(&self) -> String {
+        self.add_channel_in(None)
+    }
+
+    /// Adds an empty server channel of `guild_id` and returns its ID.
+    pub fn add_guild_channel(&self, guild_id: &str) -> String {
+        self.add_channel_in(Some(guild_id.to_string()))
+    }
+
+    fn add_channel_in(&self, guild_id: Option<String>) -> String {
+        let id = NEXT_CHANNEL.fetch_add(1, Ordering::SeqCst).to_string();
+        self.state.lock().unwrap().channels.insert(id.clone(), Channel { guild_id, messages: BTreeMap::new() });
+        id
+    }
+
+    /// Posts a message one minute after the previous one and returns its ID.
+    pub fn add_message(&self, channel_id: &str, author_id: &str, content: &str) -> String {
+        self.add_message_of_type(channel_id, author_id, content, 0)
+    }
+
+    pub fn add_message_of_type(&self, channel_id: &str, author_id: &str, content: &str, kind: u8) -> String {
+        let mut state = self.state.lock().unwrap();
+        let time = state.next_message_time
+            .unwrap_or_else(|| Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        state.next_message_time = Some(time + ChronoDuration::minutes(1));
+
+        let id = snowflake_from_timestamp(time);
+        let message = MockMessage { id, author_id: author_id.to_string(), content: content.to_string(), kind };
+        state.channels.get_mut(channel_id).expect("unknown mock channel").messages.insert(id, message);
+        id.to_string()
+    }
+
+    /// Posts `count` messages by [`AUTHOR`] and returns their IDs.
+    pub fn add_messages(&self, channel_id: &str, count: usize) -> Vec<String> {
+        (0..count).map(|i| self.add_message(channel_id, AUTHOR, &format!("message {}", i))).collect()
+    }
+
+    /// Posts `count` messages where every `every`-th one, starting with the first, is by
+    /// [`OTHER`] and the rest by [`AUTHOR`]. Returns the IDs of the author's messages.
+    pub fn add_conversation(&self, channel_id: &str, count: usize, every: usize) -> Vec<String> {
+        let mut own = Vec::new();
+        for i in 0..count {
+            if i % every == 0 {
+                self.add_message(channel_id, OTHER, &format!("reply {}", i));
+            } else {
+                own.push(self.add_message(channel_id, AUTHOR, &format!("message {}", i)));
+            }
+        }
+        own
+    }
+
+    /// Answers the next `times` DELETE requests for `message_id` with `status`.
+    pub fn fail_delete(&self, message_id: &str, status: u16, times: usize) {
+        self.script_delete(message_id, DeleteFailure { status, remaining: Some(times) });
+    }
+
+    /// Answers every DELETE request for `message_id` with `status`.
+    pub fn always_fail_delete(&self, message_id: &str, status: u16) {
+        self.script_delete(message_id, DeleteFailure { status, remaining: None });
+    }
+
+    fn script_delete(&self, message_id: &str, failure: DeleteFailure) {
+        let id = message_id.parse().expect("numeric message ID");
+        self.state.lock().unwrap().delete_failures.insert(id, failure);
+    }
+
+    /// Answers the next DELETE request with a 429 asking to wait `retry_after` seconds.
+    pub fn rate_limit_next_delete(&self, retry_after: f64) {
+        self.state.lock().unwrap().forced_rate_limits.push(retry_after);
+    }
+
+    /// Allows `limit` DELETE requests per `window`, announced in the rate-limit headers.
+    /// Requests over the limit get a 429.
+    pub fn limit_deletes(&self, limit: u32, window: Duration) {
+        self.state.lock().unwrap().delete_bucket = Some(Bucket { limit, window, used: 0, window_start: Instant::now() });
+    }
+
+    /// Answers the next `times` search requests with "index not ready".
+    pub fn index_not_ready(&self, times: usize) {
+        self.state.lock().unwrap().index_not_ready = times;
+    }
+
+    /// Serves `contents` as an attachment of `channel_id` and returns its CDN URL.
+    pub fn add_attachment(&self, channel_id: &str, filename: &str, contents: &[u8]) -> String {
+        let mut state = self.state.lock().unwrap();
+        let path = format!("/attachments/{}/{}/{}", channel_id, 500_000_000_000_000_000 + state.attachments.len(), filename);
+        state.attachments.insert(path.clone(), contents.to_vec());
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Like [`add_attachment`](Self::add_attachment), but the file is sent in 1 KiB chunks
+    /// without a `Content-Length`, so its size is only known once it has arrived.
+    pub fn add_streamed_attachment(&self, channel_id: &str, filename: &str, contents: &[u8]) -> String {
+        let url = self.add_attachment(channel_id, filename, contents);
+        let path = url.trim_start_matches(&format!("http://{}", self.addr)).to_string();
+        self.state.lock().unwrap().streamed.insert(path);
+        url
+    }
+
+    /// Answers the next `times` history requests with a truncated body.
+    pub fn malformed_history(&self, times: usize) {
+        self.state.lock().unwrap().malformed_history = times;
+    }
+
+    /// IDs of the messages still in `channel_id`, oldest first.
+    pub fn remaining(&self, channel_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.channels[channel_id].messages.keys().map(u64::to_string).collect()
+    }
+
+    /// Requests received with `method` whose path contains `fragment`.
+    pub fn count_requests(&self, method: Method, fragment: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state.requests.iter().filter(|(m, path)| *m == method && path.contains(fragment)).count()
+    }
+
+    /// 429 responses sent so far.
+    pub fn rate_limited(&self) -> usize {
+        self.state.lock().unwrap().rate_limited
+    }
+}
+
+impl Drop for MockDiscord {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn message_json(channel_id: &str, message: &MockMessage) -> Value {
+    let time = discord_message_deleter::snowflake::timestamp_from_snowflake(&message.id.to_string())
+        .expect("valid snowflake");
+    json!({
+        "id": message.id.to_string(),
+        "channel_id": channel_id,
+        "type": message.kind,
+        "content": message.content,
+        "timestamp": time.to_rfc3339(),
+        "author": { "id": message.author_id, "username": format!("user{}", &message.author_id[..4]) },
+        "attachments": [],
+        "embeds": [],
+    })
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error(status: StatusCode, code: u32, message: &str) -> Response<Body> {
+    respond(status, json!({ "code": code, "message": message }))
+}
+
+fn rate_limit(retry_after: f64) -> Response<Body> {
+    let mut response = respond(
+        StatusCode::TOO_MANY_REQUESTS,
+        json!({ "message": "You are being rate limited.", "retry_after": retry_after, "global": false }),
+    );
+    response.headers_mut().insert("retry-after", retry_after.ceil().to_string().parse().unwrap());
+    response
+}
+
+fn query(request: &Request<Body>) -> HashMap<String, String> {
+    let url = reqwest::Url::parse(&format!("http://mock{}", request.uri())).expect("request URI");
+    url.query_pairs().into_owned().collect()
+}
+
+async fn handle(state: &Mutex<State>, request: Request<Body>) -> Response<Body> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let query = query(&request);
+    let mut state = state.lock().unwrap();
+    state.requests.push((method.clone(), path.clone()));
+
+    if let Some(contents) = state.attachments.get(&path) {
+        if state.streamed.contains(&path) {
+            let (mut sender, body) = Body::channel();
+            let contents = contents.clone();
+            tokio::spawn(async move {
+                for chunk in contents.chunks(1024) {
+                    if sender.send_data(chunk.to_vec().into()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            return Response::builder().status(StatusCode::OK).body(body).unwrap();
+        }
+        return Response::builder().status(StatusCode::OK).body(Body::from(contents.clone())).unwrap();
+    }
+
+    if request.headers().get("authorization").is_none_or(|token| token != TOKEN) {
+        return error(StatusCode::UNAUTHORIZED, 0, "401: Unauthorized");
+    }
+
+    let Some(route) = path.strip_prefix("/api/v10/") else {
+        return error(StatusCode::NOT_FOUND, 0, "404: Not Found");
+    };
+    let segments: Vec<&str> = route.split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (Method::GET, ["users", "@me"]) => respond(
+            StatusCode::OK,
+            json!({ "id": AUTHOR, "username": "mockuser", "global_name": "Mock User", "flags": 64 }),
+        ),
+        (Method::GET, ["channels", channel_id]) => match state.channels.get(*channel_id) {
+            Some(channel) => respond(StatusCode::OK, json!({
+                "id": channel_id,
+                "type": if channel.guild_id.is_some() { 0 } else { 1 },
+                "guild_id": channel.guild_id,
+                "name": channel.guild_id.as_ref().map(|_| "general"),
+            })),
+            None => error(StatusCode::NOT_FOUND, 10003, "Unknown Channel"),
+        },
+        (Method::GET, ["guilds", guild_id, "channels"]) => {
+            let channels: Vec<Value> = state.channels.iter()
+                .filter(|(_, channel)| channel.guild_id.as_deref() == Some(*guild_id))
+                .map(|(id, _)| json!({ "id": id, "type": 0, "name": "general" }))
+                .collect();
+            respond(StatusCode::OK, Value::Array(channels))
+        }
+        (Method::GET, ["channels", _, "messages"]) if state.malformed_history > 0 => {
+            state.malformed_history -= 1;
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("content-type", "application/json")
+                .body(Body::from(r#"[{"id": "1"#))
+                .unwrap()
+        }
+        (Method::GET, ["channels", channel_id, "messages"]) => history(&state, channel_id, &query),
+        (Method::GET, ["channels", channel_id, "messages", "search"]) => {
+            search(&mut state, Some(channel_id), &query)
+        }
+        (Method::GET, ["guilds", _, "messages", "search"]) => search(&mut state, None, &query),
+        (Method::DELETE, ["channels", channel_id, "messages", message_id]) => {
+            delete(&mut state, channel_id, message_id)
+        }
+        _ => error(StatusCode::NOT_FOUND, 0, "404: Not Found"),
+    }
+}
+
+/// `GET /channels/:id/messages`: up to `limit` messages older than `before`, newest first.
+fn history(state: &State, channel_id: &str, query: &HashMap<String, String>) -> Response<Body> {
+    let Some(channel) = state.channels.get(channel_id) else {
+        return error(StatusCode::NOT_FOUND, 10003, "Unknown Channel");
+    };
+    let limit = query.get("limit").and_then(|l| l.parse().ok()).unwrap_or(50usize).min(100);
+    let before = query.get("before").and_then(|b| b.parse().ok()).unwrap_or(u64::MAX);
+
+    let page: Vec<Value> = channel.messages.range(..before)
+        .rev()
+        .take(limit)
+        .map(|(_, message)| message_json(channel_id, message))
+        .collect();
+    respond(StatusCode::OK, Value::Array(page))
+}
+
+/// The search endpoints: the author's messages, newest first, 25 per page.
+fn search(state: &mut State, channel_id: Option<&str>, query: &HashMap<String, String>) -> Response<Body> {
+    if state.index_not_ready > 0 {
+        state.index_not_ready -= 1;
+        return respond(
+            StatusCode::ACCEPTED,
+            json!({ "message": "Index not yet available. Try again later", "code": 110000, "retry_after": 0.1 }),
+        );
+    }
+
+    let Some(channel_id) = channel_id.or(query.get("channel_id").map(String::as_str)) else {
+        return error(StatusCode::BAD_REQUEST, 50035, "Invalid Form Body");
+    };
+    let Some(channel) = state.channels.get(channel_id) else {
+        return error(StatusCode::NOT_FOUND, 10003, "Unknown Channel");
+    };
+    let author_id = query.get("author_id");
+    let min_id = query.get("min_id").and_then(|id| id.parse().ok()).unwrap_or(0);
+    let max_id = query.get("max_id").and_then(|id| id.parse().ok()).unwrap_or(u64::MAX);
+    let offset = query.get("offset").and_then(|o| o.parse().ok()).unwrap_or(0);
+
+    let hits: Vec<&MockMessage> = channel.messages.range(min_id..max_id)
+        .rev()
+        .map(|(_, message)| message)
+        .filter(|message| author_id.is_none_or(|author| *author == message.author_id))
+        .collect();
+    let page: Vec<Value> = hits.iter()
+        .skip(offset)
+        .take(SEARCH_PAGE)
+        .map(|message| json!([message_json(channel_id, message)]))
+        .collect();
+    respond(StatusCode::OK, json!({ "total_results": hits.len(), "messages": page }))
+}
+
+/// `DELETE /channels/:id/messages/:id`, going through the scripted failures and rate limits.
+fn delete(state: &mut State, channel_id: &str, message_id: &str) -> Response<Body> {
+    if !state.forced_rate_limits.is_empty() {
+        let retry_after = state.forced_rate_limits.remove(0);
+        state.rate_limited += 1;
+        return rate_limit(retry_after);
+    }
+
+    let mut headers = Vec::new();
+    if let Some(bucket) = &mut state.delete_bucket {
+        let now = Instant::now();
+        if now.duration_since(bucket.window_start) >= bucket.window {
+            bucket.used = 0;
+            bucket.window_start = now;
+        }
+        let reset_after = bucket.window.saturating_sub(now.duration_since(bucket.window_start));
+        if bucket.used >= bucket.limit {
+            state.rate_limited += 1;
+            return rate_limit(reset_after.as_secs_f64());
+        }
+        bucket.used += 1;
+        headers = vec![
+            ("x-ratelimit-bucket", "mock-delete".to_string()),
+            ("x-ratelimit-limit", bucket.limit.to_string()),
+            ("x-ratelimit-remaining", (bucket.limit - bucket.used).to_string()),
+            ("x-ratelimit-reset-after", format!("{:.3}", reset_after.as_secs_f64())),
+        ];
+    }
+
+    let mut response = delete_message(state, channel_id, message_id);
+    for (name, value) in headers {
+        response.headers_mut().insert(name, value.parse().unwrap());
+    }
+    response
+}
+
+fn delete_message(state: &mut State, channel_id: &str, message_id: &str) -> Response<Body> {
+    let Ok(id) = message_id.parse::<u64>() else {
+        return error(StatusCode::NOT_FOUND, 10008, "Unknown Message");
+    };
+
+    if let Some(failure) = state.delete_failures.get_mut(&id) {
+        let status = failure.status;
+        match &mut failure.remaining {
+            Some(0) => {}
+            Some(remaining) => {
+                *remaining -= 1;
+                return scripted_failure(status);
+            }
+            None => return scripted_failure(status),
+        }
+    }
+
+    let Some(channel) = state.channels.get_mut(channel_id) else {
+        return error(StatusCode::NOT_FOUND, 10003, "Unknown Channel");
+    };
+    match channel.messages.get(&id) {
+        None => error(StatusCode::NOT_FOUND, 10008, "Unknown Message"),
+        Some(message) if message.author_id != AUTHOR => error(StatusCode::FORBIDDEN, 50013, "Missing Permissions"),
+        Some(message) if [1, 2, 3, 4, 5, 21].contains(&message.kind) => {
+            error(StatusCode::BAD_REQUEST, 50021, "Cannot execute action on a system message")
+        }
+        Some(_) => {
+            channel.messages.remove(&id);
+            Response::builder().status(StatusCode::NO_CONTENT).body(Body::empty()).unwrap()
+        }
+    }
+}
+
+fn scripted_failure(status: u16) -> Response<Body> {
+    let status = StatusCode::from_u16(status).expect("valid status");
+    match status {
+        StatusCode::FORBIDDEN => error(status, 50001, "Missing Access"),
+        StatusCode::NOT_FOUND => error(status, 10008, "Unknown Message"),
+        _ => error(status, 0, status.canonical_reason().unwrap_or("Error")),
+    }
+}
@@ -0,0 +1,20 @@
+//! The adaptive pacer's delay and its measured deletion rate.
+
+use discord_message_deleter::pacing::AdaptivePacer;
+use reqwest::header::HeaderMap;
+
+#[test]
+fn the_rate_counts_deletions_not_the_delay() {
+    let pacer = AdaptivePacer::new(0, 5000);
+    assert_eq!(pacer.status(), (1000, 0, 0));
+
+    for _ in 0..3 {
+        pacer.on_success(&HeaderMap::new());
+        pacer.on_deleted();
+    }
+    // A response that deleted nothing speeds up the pacer but adds nothing to the rate.
+    pacer.on_success(&HeaderMap::new());
+
+    let (delay_ms, per_minute, rate_limits) = pacer.status();
+    assert_eq!((delay_ms, per_minute, rate_limits), (656, 3, 0));
+}
@@ -0,0 +1,173 @@
+//! The shared HTTP transport: timeouts, retries after transport errors, metrics and settings.
+
+mod mock;
+
+use discord_message_deleter::{
+    transport::{HttpOptions, Transport, USER_AGENT},
+    DiscordClient, Error, RunOptions,
+};
+use hyper::Method;
+use mock::{MockDiscord, AUTHOR, TOKEN};
+use std::time::{Duration, Instant};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc,
+    time::sleep,
+};
+
+fn options(timeout: Duration, retries: u32) -> HttpOptions {
+    HttpOptions { timeout, retries, ..HttpOptions::default() }
+}
+
+/// Starts a server that accepts connections and reads requests but never answers them.
+/// Every request received is sent to the returned channel.
+async fn hung_server() -> (String, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_base = format!("http://{}/api/v10", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buffer = vec![0; 4096];
+            let read = socket.read(&mut buffer).await.unwrap_or(0);
+            let _ = tx.send(String::from_utf8_lossy(&buffer[..read]).to_lowercase());
+            open.push(socket);
+        }
+    });
+    (api_base, rx)
+}
+
+#[tokio::test]
+async fn hung_connections_time_out_instead_of_stalling_the_listing() {
+    let (api_base, mut requests) = hung_server().await;
+    let transport = Transport::new(&options(Duration::from_millis(300), 1)).unwrap();
+    let client = DiscordClient::builder(TOKEN)
+        .api_base(api_base)
+        .channel("123456789012345678")
+        .author(AUTHOR)
+        .options(RunOptions { retry_rounds: 0, ..RunOptions::default() })
+        .transport(transport.clone())
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    let result = client.get_messages(None).await;
+
+    assert!(matches!(result, Err(Error::Network(ref e)) if e.is_timeout()), "{:?}", result.err());
+    assert!(started.elapsed() < Duration::from_secs(60));
+    // Only the transport retries network errors, so the request is sent twice.
+    let metrics = transport.metrics();
+    assert_eq!((metrics.requests, metrics.retries, metrics.timeouts), (2, 1, 2));
+    assert!(metrics.responses.is_empty());
+
+    let request = requests.recv().await.unwrap();
+    assert!(request.contains(&format!("user-agent: {}", USER_AGENT.to_lowercase())));
+    assert!(request.contains(&format!("authorization: {}", TOKEN)));
+}
+
+#[tokio::test]
+async fn deletions_that_timed_out_are_not_sent_again() {
+    let (api_base, mut requests) = hung_server().await;
+    let transport = Transport::new(&options(Duration::from_millis(300), 2)).unwrap();
+
+    let url = format!("{}/channels/123456789012345678/messages/123456789012345679", api_base);
+    let result = transport.send(Method::DELETE, &url, TOKEN).await;
+
+    assert!(matches!(result, Err(Error::Network(ref e)) if e.is_timeout()), "{:?}", result.err());
+    let metrics = transport.metrics();
+    assert_eq!((metrics.requests, metrics.retries), (1, 0));
+    assert!(requests.recv().await.unwrap().starts_with("delete "));
+    assert!(requests.try_recv().is_err());
+}
+
+/// Starts a server that answers every request with `chunks` pieces of 16 bytes, `pause` apart.
+async fn slow_server(chunks: usize, pause: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/attachments/1/2/large.bin", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = vec![0; 4096];
+                let _ = socket.read(&mut buffer).await;
+                let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", chunks * 16);
+                let _ = socket.write_all(head.as_bytes()).await;
+                for _ in 0..chunks {
+                    sleep(pause).await;
+                    let _ = socket.write_all(&[7; 16]).await;
+                }
+            });
+        }
+    });
+    url
+}
+
+#[tokio::test]
+async fn downloads_are_not_cut_off_by_the_request_timeout() {
+    let url = slow_server(5, Duration::from_millis(150)).await;
+    let transport = Transport::new(&options(Duration::from_millis(300), 0)).unwrap();
+
+    let download = transport.get(&url).await.unwrap().bytes().await;
+    assert_eq!(download.unwrap().len(), 80);
+
+    let api = match transport.send(Method::GET, &url, TOKEN).await {
+        Ok(response) => response.bytes().await.map(|_| ()).map_err(Error::from),
+        Err(e) => Err(e),
+    };
+    assert!(matches!(api, Err(Error::Network(ref e)) if e.is_timeout()), "{:?}", api);
+}
+
+#[tokio::test]
+async fn refused_connections_are_retried_then_reported() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let transport = Transport::new(&options(Duration::from_secs(5), 2)).unwrap();
+
+    let result = transport.send(Method::GET, &format!("http://127.0.0.1:{}/api/v10/users/@me", port), TOKEN).await;
+
+    assert!(matches!(result, Err(Error::Network(ref e)) if e.is_connect()));
+    let metrics = transport.metrics();
+    assert_eq!((metrics.requests, metrics.retries, metrics.transport_errors), (3, 2, 3));
+}
+
+#[tokio::test]
+async fn metrics_count_the_responses_of_a_run() {
+    let mock = MockDiscord::start().await;
+    let channel_id = mock.add_channel();
+    let own = mock.add_messages(&channel_id, 5);
+    mock.fail_delete(&own[2], 502, 1);
+
+    let transport = Transport::new(&HttpOptions::default()).unwrap();
+    let client = mock.client(&channel_id)
+        .options(RunOptions { retry_rounds: 0, ..RunOptions::default() })
+        .transport(transport.clone())
+        .build()
+        .unwrap();
+    client.delete_all_messages().await.unwrap();
+
+    let metrics = transport.metrics();
+    let sent = mock.count_requests(Method::GET, "/") + mock.count_requests(Method::DELETE, "/");
+    assert_eq!(metrics.requests as usize, sent);
+    assert_eq!(metrics.responses.get(&502), Some(&1));
+    assert_eq!(metrics.responses.get(&204).copied().unwrap_or_default() as usize, own.len());
+    assert_eq!(metrics.transport_errors, 0);
+    assert!(metrics.average_latency().is_some());
+}
+
+#[tokio::test]
+async fn invalid_proxy_and_ca_bundle_are_rejected() {
+    let proxy = HttpOptions { proxy: Some("not a url".to_string()), ..HttpOptions::default() };
+    assert!(matches!(Transport::new(&proxy), Err(Error::InvalidInput(_))));
+
+    let missing = HttpOptions { ca_bundle: Some("no/such/bundle.pem".into()), ..HttpOptions::default() };
+    assert!(matches!(Transport::new(&missing), Err(Error::Io { .. })));
+
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), "not a certificate").unwrap();
+    let empty = HttpOptions { ca_bundle: Some(file.path().to_path_buf()), ..HttpOptions::default() };
+    assert!(matches!(Transport::new(&empty), Err(Error::InvalidInput(_))));
+
+    let socks = HttpOptions { proxy: Some("socks5://127.0.0.1:9050".to_string()), ..HttpOptions::default() };
+    assert!(Transport::new(&socks).is_ok());
+}